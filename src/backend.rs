@@ -1,5 +1,13 @@
 use std::io::{self, Write};
 
+use crossterm::{
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    queue,
+    style::Print,
+};
 use delegate::delegate;
 use ratatui::{
     backend::{Backend, CrosstermBackend, WindowSize},
@@ -20,6 +28,13 @@ pub struct SshBackend {
 }
 
 impl SshBackend {
+    const QUERY_KEYBOARD_ENHANCEMENT_FLAGS: &'static str = "\x1b[?u";
+    const QUERY_TERMINAL_VERSION: &'static str = "\x1b[>0q";
+    // Asks whether a 1x1 image could be displayed, without actually storing it
+    const QUERY_KITTY_GRAPHICS: &'static str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+    const QUERY_DEVICE_ATTRIBUTES: &'static str = "\x1b[c";
+    const QUERY_STATUS: &'static str = "\x1b[5n";
+
     pub fn new(
         channel_id: ChannelId,
         session_handle: Handle,
@@ -40,6 +55,87 @@ impl SshBackend {
             window_size,
         }
     }
+
+    /// The height of each cell relative to its width, if the client reported the size of its
+    /// window in pixels.
+    ///
+    /// Sizes that don't look like a real font are ignored, as some clients report nonsense.
+    pub fn cell_aspect(&self) -> Option<f32> {
+        cell_aspect(self.window_size)
+    }
+
+    /// The size of each cell in whole pixels, if the client reported the size of its window in
+    /// pixels.
+    pub fn cell_size(&self) -> Option<Size> {
+        let WindowSize {
+            columns_rows,
+            pixels,
+        } = self.window_size;
+        let size = Size::new(
+            pixels.width.checked_div(columns_rows.width)?,
+            pixels.height.checked_div(columns_rows.height)?,
+        );
+        (size.width > 0 && size.height > 0).then_some(size)
+    }
+
+    /// Write escape sequences straight to the terminal, such as images drawn by the
+    /// [`GraphicsRenderer`](crate::graphics::GraphicsRenderer).
+    pub fn write_raw(&mut self, output: &str) -> io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        queue!(self.inner, Print(output))?;
+        Backend::flush(&mut self.inner)
+    }
+
+    /// Prepare the client's terminal for the game.
+    ///
+    /// This enables mouse reporting, so players can position their paddle with the mouse, and asks
+    /// the terminal to report key presses, repeats and releases using the kitty keyboard protocol,
+    /// and queries whether it supports this. Terminals that support the protocol reply
+    /// with their current flags, which arrive as an [`InputEvent::KeyboardEnhancementFlags`].
+    ///
+    /// It also asks the terminal for its name and version, which arrive as an
+    /// [`InputEvent::TerminalVersion`] and help to detect its [`Capabilities`].
+    ///
+    /// Finally it asks whether the terminal can display images with the kitty graphics protocol or
+    /// sixels, for the graphics renderer.
+    ///
+    /// [`InputEvent::KeyboardEnhancementFlags`]: crate::input::InputEvent::KeyboardEnhancementFlags
+    /// [`InputEvent::TerminalVersion`]: crate::input::InputEvent::TerminalVersion
+    /// [`Capabilities`]: crate::capabilities::Capabilities
+    pub fn init_session(&mut self) -> io::Result<()> {
+        queue!(
+            self.inner,
+            EnableMouseCapture,
+            PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                    | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+                    | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            ),
+            Print(Self::QUERY_KEYBOARD_ENHANCEMENT_FLAGS),
+            Print(Self::QUERY_TERMINAL_VERSION),
+            Print(Self::QUERY_KITTY_GRAPHICS),
+            Print(Self::QUERY_DEVICE_ATTRIBUTES),
+        )?;
+        Backend::flush(&mut self.inner)
+    }
+
+    /// Ask the terminal for its status, which it answers straight away with an
+    /// [`InputEvent::StatusReport`], to measure the latency to the client.
+    ///
+    /// [`InputEvent::StatusReport`]: crate::input::InputEvent::StatusReport
+    pub fn query_status(&mut self) -> io::Result<()> {
+        queue!(self.inner, Print(Self::QUERY_STATUS))?;
+        Backend::flush(&mut self.inner)
+    }
+
+    /// Restore the client's terminal to the state it was in before [`SshBackend::init_session`].
+    pub fn restore_session(&mut self) -> io::Result<()> {
+        queue!(self.inner, PopKeyboardEnhancementFlags, DisableMouseCapture)?;
+        Backend::flush(&mut self.inner)
+    }
 }
 
 impl Backend for SshBackend {
@@ -74,6 +170,29 @@ impl Backend for SshBackend {
     }
 }
 
+/// The height of a cell relative to its width, for a window of the given size.
+///
+/// See [`SshBackend::cell_aspect`].
+fn cell_aspect(window_size: WindowSize) -> Option<f32> {
+    let WindowSize {
+        columns_rows,
+        pixels,
+    } = window_size;
+    if [
+        columns_rows.width,
+        columns_rows.height,
+        pixels.width,
+        pixels.height,
+    ]
+    .contains(&0)
+    {
+        return None;
+    }
+    let cell_width = f32::from(pixels.width) / f32::from(columns_rows.width);
+    let cell_height = f32::from(pixels.height) / f32::from(columns_rows.height);
+    Some(cell_height / cell_width).filter(|aspect| (1.0..=4.0).contains(aspect))
+}
+
 #[derive(Clone)]
 pub struct TerminalHandle {
     handle: Handle,
@@ -124,3 +243,41 @@ impl Write for TerminalHandle {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(columns: u16, rows: u16, width: u16, height: u16) -> WindowSize {
+        WindowSize {
+            columns_rows: Size::new(columns, rows),
+            pixels: Size::new(width, height),
+        }
+    }
+
+    #[test]
+    fn cell_aspect_from_pixels() {
+        assert_eq!(cell_aspect(window(80, 24, 800, 480)), Some(2.0));
+        assert_eq!(cell_aspect(window(100, 50, 800, 800)), Some(2.0));
+        assert_eq!(cell_aspect(window(80, 24, 640, 192)), Some(1.0));
+        assert_eq!(cell_aspect(window(80, 24, 640, 768)), Some(4.0));
+    }
+
+    #[test]
+    fn cell_aspect_without_pixels() {
+        // clients that don't know their size in pixels report zero
+        assert_eq!(cell_aspect(window(80, 24, 0, 0)), None);
+        assert_eq!(cell_aspect(window(80, 24, 800, 0)), None);
+        assert_eq!(cell_aspect(window(80, 24, 0, 480)), None);
+        assert_eq!(cell_aspect(window(0, 0, 800, 480)), None);
+    }
+
+    #[test]
+    fn cell_aspect_ignores_unlikely_fonts() {
+        // cells wider than they are tall, or more than four times as tall
+        assert_eq!(cell_aspect(window(80, 24, 800, 120)), None);
+        assert_eq!(cell_aspect(window(80, 24, 80, 480)), None);
+        // some clients send the window size in cells as its size in pixels
+        assert_eq!(cell_aspect(window(80, 24, 80, 24)), Some(1.0));
+    }
+}
@@ -3,14 +3,56 @@ use std::time::Duration;
 use ratatui::prelude::*;
 
 use crate::{
+    level::Level,
     paddle::Paddle,
-    physics::{Point, Velocity},
+    physics::{Bounds, Orientation, Point, Side, Velocity, DEFAULT_CELL_ASPECT},
+    subcell::{Resolution, SubCells},
 };
 
-#[derive(Debug)]
+/// Something the ball bounced off during an update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    Wall,
+    /// The paddle at the given index in the paddles passed to [`Ball::update`].
+    Paddle(usize),
+}
+
+/// How the ball bounces off the paddles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Physics {
+    /// The angle depends only on where the ball hits the paddle, in seven steps.
+    #[default]
+    Classic,
+    /// The angle depends on where the ball hits the paddle and how fast the paddle is moving, and
+    /// a moving paddle puts spin on the ball that makes it curve.
+    Modern,
+}
+
+impl Physics {
+    pub const ALL: [Physics; 2] = [Physics::Classic, Physics::Modern];
+
+    /// The name used to choose the physics when starting a match.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Physics::Classic => "classic",
+            Physics::Modern => "modern",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|physics| physics.name() == name)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Ball {
     pub pos: Point,
     pub vel: Velocity,
+    /// How much faster than its velocity the ball moves, such as while a power-up is active.
+    pub speed: f32,
+    /// How fast the ball's direction is turning in radians per second, positive for clockwise on
+    /// screen. Only [`Physics::Modern`] puts spin on the ball.
+    pub spin: f32,
 }
 
 impl Default for Ball {
@@ -21,97 +63,648 @@ impl Default for Ball {
 
 impl Ball {
     const DEFAULT_INITIAL_VELOCITY: Velocity = Velocity::new(0.26, -0.23);
+    // The fastest the ball moves along a paddle's side after a hit
+    const MAX_ALONG: f32 = 0.69;
+    // How much of the paddle's velocity is passed on to the ball in modern physics
+    const VELOCITY_TRANSFER: f32 = 0.5;
+    // How much spin the paddle's velocity puts on the ball, and the most spin it can have
+    const SPIN_PER_SPEED: f32 = 1.2;
+    const MAX_SPIN: f32 = 1.0;
+    // How quickly the spin wears off, as a fraction per second
+    const SPIN_DECAY: f32 = 1.5;
+    // The steepest angle from straight across the court that spin turns the ball to, in radians,
+    // about that of the steepest return off a paddle
+    const MAX_HEADING: f32 = 1.2;
 
     /// Crete a new ball at the center of the screen with the default initial velocity.
     pub const fn new() -> Self {
         Self {
             pos: Point::CENTER,
             vel: Self::DEFAULT_INITIAL_VELOCITY,
+            speed: 1.0,
+            spin: 0.0,
         }
     }
 
+    /// Create a new ball at the center of the screen moving with the given velocity.
+    pub const fn with_velocity(vel: Velocity) -> Self {
+        Self { vel, ..Self::new() }
+    }
+
     /// Serve the ball from the center of the screen with the existing velocity.
     pub fn serve(&mut self) {
         self.pos = Point::CENTER;
+        self.spin = 0.0;
     }
 
     /// Move the ball by its current velocity.
     ///
-    /// The ball will bounce off the top and bottom edges of the screen, reversing the vertical
-    /// velocity component.
+    /// The ball will bounce off any side of the court that isn't guarded by one of the paddles,
+    /// reversing the velocity component towards that side. On a guarded side, the ball bounces
+    /// off the paddle if it hits it, and otherwise carries on out of the court if it is within the
+    /// level's goal. The ball also bounces off the level's obstacles and goes through its
+    /// portals. How the ball comes off a paddle depends on the [`Physics`].
     ///
     /// The ball will move by the velocity components scaled by the time since the last update.
     /// This ensures that the ball moves at the same speed regardless of the screen size or
     /// refresh rate.
-    pub fn update(&mut self, duration: Duration, player1: &Paddle, player2: &Paddle) {
+    ///
+    /// Returns what the ball bounced off, if anything. A paddle hit takes precedence over a wall
+    /// bounce in the same update.
+    pub fn update(
+        &mut self,
+        duration: Duration,
+        paddles: &[&Paddle],
+        level: &Level,
+        physics: Physics,
+    ) -> Option<Hit> {
+        let mut hit = None;
         let dt = duration.as_secs_f32();
-        self.pos.x += self.vel.x * dt;
-        self.pos.y += self.vel.y * dt;
+        let previous = self.pos;
+        if self.spin != 0.0 {
+            // turn the velocity without changing its speed
+            let (sin, cos) = (self.spin * dt).sin_cos();
+            let vel = self.vel;
+            self.vel = Velocity::new(vel.x * cos - vel.y * sin, vel.x * sin + vel.y * cos);
+            self.spin *= (-Self::SPIN_DECAY * dt).exp();
+        }
+        self.pos.x += self.vel.x * self.speed * dt;
+        self.pos.y += self.vel.y * self.speed * dt;
 
-        // bounce off the top and bottom edges
-        if self.pos.y < 0.0 {
-            self.pos.y = -self.pos.y;
-            self.vel.y = -self.vel.y;
-        } else if self.pos.y > 1.0 {
-            self.pos.y = 2.0 - self.pos.y;
-            self.vel.y = -self.vel.y;
+        for obstacle in &level.obstacles {
+            if self.bounce_off(obstacle.bounds, previous) {
+                hit = Some(Hit::Wall);
+            }
+        }
+        // only go through a portal when first reaching it, so the ball doesn't bounce straight
+        // back through a portal at the exit
+        if let Some(portal) = level
+            .portals
+            .iter()
+            .find(|portal| portal.is_at_entry(self.pos) && !portal.is_at_entry(previous))
+        {
+            self.pos = portal.exit;
         }
 
-        // bounce off the paddles
-        // todo: change direction based on where the ball hits the paddle
-        // todo: increase horizontal speed based on number of hits
+        // todo: increase speed based on number of hits
         // todo: calculate the intersection point of the ball and the paddle rather than just
-        // checking if the ball is within the paddle's height
-        if self.pos.x < 0.0 {
-            if (player1.pos.y - Paddle::HEIGHT / 2.0 < self.pos.y)
-                && (self.pos.y < player1.pos.y + Paddle::HEIGHT / 2.0)
-            {
+        // checking if the ball is within the paddle's length
+        for side in Side::ALL {
+            if !side.is_beyond(self.pos) {
+                continue;
+            }
+            let offset = side.orientation().along(self.pos);
+            let guarded = paddles.iter().any(|paddle| paddle.side == side);
+            if !guarded || !level.is_in_goal(offset) {
+                self.bounce(side);
+                hit = hit.or(Some(Hit::Wall));
+                continue;
+            }
+            let hit_paddle = paddles
+                .iter()
+                .position(|paddle| paddle.side == side && paddle.covers(offset));
+            if let Some(index) = hit_paddle {
+                let paddle = paddles[index];
+                self.bounce(side);
+                let angle = (offset - paddle.offset()) / (paddle.length / 2.0);
+                let along = match physics {
+                    // change direction based on where the ball hits the paddle, mapped onto
+                    // the range of valid velocities along the paddle
+                    Physics::Classic => {
+                        let step = ((angle * 3.0).round() as i32 + 3).clamp(0, 6) as usize;
+                        Velocity::VALID_Y[step]
+                    }
+                    Physics::Modern => (angle * Self::MAX_ALONG
+                        + paddle.vel * Self::VELOCITY_TRANSFER)
+                        .clamp(-Self::MAX_ALONG, Self::MAX_ALONG),
+                };
+                match paddle.orientation() {
+                    Orientation::Vertical => self.vel.y = along,
+                    Orientation::Horizontal => self.vel.x = along,
+                }
+                if physics == Physics::Modern {
+                    self.spin_off(paddle);
+                }
+                hit = Some(Hit::Paddle(index));
+            }
+        }
+        hit
+    }
+
+    /// Put spin on the ball as it leaves the paddle, curving it the way the paddle was moving.
+    ///
+    /// The spin wears off exponentially, so it turns the ball by its initial amount divided by
+    /// the decay in total. That is limited so the ball never curves steeper than the steepest
+    /// return, which would otherwise bring steep returns back towards the hitter's own goal.
+    fn spin_off(&mut self, paddle: &Paddle) {
+        // the velocity away from the paddle and along its side, turned the same way as the spin
+        // turns the ball when positive
+        let (away, along) = match paddle.orientation() {
+            Orientation::Vertical => (self.vel.x, self.vel.y),
+            Orientation::Horizontal => (-self.vel.y, self.vel.x),
+        };
+        let heading = along.atan2(away.abs());
+        let turn = (paddle.vel * Self::SPIN_PER_SPEED / Self::SPIN_DECAY).clamp(
+            (-Self::MAX_HEADING - heading).min(0.0),
+            (Self::MAX_HEADING - heading).max(0.0),
+        );
+        self.spin =
+            (turn * Self::SPIN_DECAY * away.signum()).clamp(-Self::MAX_SPIN, Self::MAX_SPIN);
+    }
+
+    /// Bounce off the outside of the bounds if the ball has moved into them from the given
+    /// position.
+    ///
+    /// The ball is reflected off the edge it crossed. If it didn't cross one, because a moving
+    /// obstacle moved onto it, it is pushed out of the nearest edge instead.
+    fn bounce_off(&mut self, bounds: Bounds, previous: Point) -> bool {
+        if !bounds.contains(self.pos) {
+            return false;
+        }
+        let before = self.vel;
+        let (pos, vel) = (&mut self.pos, &mut self.vel);
+        if previous.x < bounds.x {
+            pos.x = 2.0 * bounds.x - pos.x;
+            vel.x = -vel.x.abs();
+        } else if previous.x >= bounds.right() {
+            pos.x = 2.0 * bounds.right() - pos.x;
+            vel.x = vel.x.abs();
+        } else if previous.y < bounds.y {
+            pos.y = 2.0 * bounds.y - pos.y;
+            vel.y = -vel.y.abs();
+        } else if previous.y >= bounds.bottom() {
+            pos.y = 2.0 * bounds.bottom() - pos.y;
+            vel.y = vel.y.abs();
+        } else {
+            let left = pos.x - bounds.x;
+            let right = bounds.right() - pos.x;
+            let top = pos.y - bounds.y;
+            let bottom = bounds.bottom() - pos.y;
+            let nearest = left.min(right).min(top).min(bottom);
+            // the top and left edges are inside the bounds
+            if nearest == left {
+                pos.x = bounds.x - f32::EPSILON;
+                vel.x = -vel.x.abs();
+            } else if nearest == right {
+                pos.x = bounds.right();
+                vel.x = vel.x.abs();
+            } else if nearest == top {
+                pos.y = bounds.y - f32::EPSILON;
+                vel.y = -vel.y.abs();
+            } else {
+                pos.y = bounds.bottom();
+                vel.y = vel.y.abs();
+            }
+        }
+        if self.vel != before {
+            self.spin = -self.spin;
+        }
+        true
+    }
+
+    /// Reflect the ball back into the court from beyond the given side.
+    ///
+    /// Reflecting the ball reverses which way its spin turns it.
+    fn bounce(&mut self, side: Side) {
+        self.spin = -self.spin;
+        match side {
+            Side::Left => {
                 self.pos.x = -self.pos.x;
                 self.vel.x = -self.vel.x;
-
-                let distance = self.pos.y - player1.pos.y;
-                let angle = distance / (Paddle::HEIGHT / 2.0);
-                // map onto the range of valid vertical velocities
-                let index = ((angle * 3.0).round() as i32 + 3) as usize;
-                self.vel.y = Velocity::VALID_Y[index];
             }
-        } else if self.pos.x > 1.0
-            && (player2.pos.y - Paddle::HEIGHT / 2.0 < self.pos.y)
-            && (self.pos.y < player2.pos.y + Paddle::HEIGHT / 2.0)
-        {
-            self.pos.x = 2.0 - self.pos.x;
-            self.vel.x = -self.vel.x;
+            Side::Right => {
+                self.pos.x = 2.0 - self.pos.x;
+                self.vel.x = -self.vel.x;
+            }
+            Side::Top => {
+                self.pos.y = -self.pos.y;
+                self.vel.y = -self.vel.y;
+            }
+            Side::Bottom => {
+                self.pos.y = 2.0 - self.pos.y;
+                self.vel.y = -self.vel.y;
+            }
+        }
+    }
+
+    /// The side of the court the ball has left through, if it is out.
+    pub fn exit_side(&self) -> Option<Side> {
+        Side::ALL.into_iter().find(|side| side.is_beyond(self.pos))
+    }
+}
+
+/// How the ball is drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BallGlyphs {
+    /// Whole columns, with the vertical position drawn in eighths of a cell using block elements
+    /// and Symbols for Legacy Computing.
+    Eighths,
+    /// Half cells on both axes using quadrant block elements.
+    #[default]
+    Quadrants,
+    /// A half cell across and a third of a cell down using legacy computing sextants.
+    Sextants,
+    /// A half cell across and a quarter of a cell down using braille patterns.
+    Braille,
+    /// Whole cells using the letter `o`, for terminals without Unicode support.
+    Ascii,
+}
+
+impl BallGlyphs {
+    pub const ALL: [BallGlyphs; 5] = [
+        BallGlyphs::Eighths,
+        BallGlyphs::Quadrants,
+        BallGlyphs::Sextants,
+        BallGlyphs::Braille,
+        BallGlyphs::Ascii,
+    ];
 
-            let distance = self.pos.y - player2.pos.y;
-            let angle = distance / (Paddle::HEIGHT / 2.0);
-            // map onto the range of valid vertical velocities
-            let index = ((angle * 3.0).round() as i32 + 3) as usize;
-            self.vel.y = Velocity::VALID_Y[index];
+    /// The name used for the glyphs in settings and profile files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            BallGlyphs::Eighths => "eighths",
+            BallGlyphs::Quadrants => "quadrants",
+            BallGlyphs::Sextants => "sextants",
+            BallGlyphs::Braille => "braille",
+            BallGlyphs::Ascii => "ascii",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|glyphs| glyphs.name() == name)
+    }
+
+    const fn resolution(self) -> Option<Resolution> {
+        match self {
+            BallGlyphs::Eighths | BallGlyphs::Ascii => None,
+            BallGlyphs::Quadrants => Some(Resolution::Quadrants),
+            BallGlyphs::Sextants => Some(Resolution::Sextants),
+            BallGlyphs::Braille => Some(Resolution::Braille),
         }
     }
 }
 
-impl Widget for &Ball {
+impl Ball {
+    // The width of the ball in cells. The height depends on the shape of the cells, so that the
+    // ball is square.
+    const WIDTH: f32 = 1.0;
+
+    /// A widget that draws the ball using the given glyphs.
+    pub fn widget(&self, glyphs: BallGlyphs) -> BallWidget<'_> {
+        BallWidget {
+            ball: self,
+            glyphs,
+            style: Style::default(),
+            cell_aspect: DEFAULT_CELL_ASPECT,
+        }
+    }
+}
+
+/// Draws a [`Ball`] with a particular set of glyphs. See [`Ball::widget`].
+pub struct BallWidget<'a> {
+    ball: &'a Ball,
+    glyphs: BallGlyphs,
+    style: Style,
+    cell_aspect: f32,
+}
+
+impl BallWidget<'_> {
+    pub fn style(mut self, style: impl Into<Style>) -> Self {
+        self.style = style.into();
+        self
+    }
+
+    /// Set the height of the terminal's cells relative to their width.
+    pub const fn cell_aspect(mut self, cell_aspect: f32) -> Self {
+        self.cell_aspect = cell_aspect;
+        self
+    }
+}
+
+impl Widget for BallWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        if !(0.0..=1.0).contains(&self.pos.x) || !(0.0..=1.0).contains(&self.pos.y) {
+        let pos = self.ball.pos;
+        if !(0.0..=1.0).contains(&pos.x) || !(0.0..=1.0).contains(&pos.y) {
             return;
         }
-        // use block characters that represent 1/8th of a cell to draw the paddles
-        const TOP_BARS: [&str; 9] = ["█", "▇", "▆", "▅", "▄", "▃", "▂", "▁", " "];
-        const BOTTOM_BARS: [&str; 9] = [" ", "▔", "🮂", "🮃", "▀", "🮄", "🮅", "🮆", "█"];
-        let _x = self.pos.x * (area.width.saturating_sub(1)) as f32;
-        let y = self.pos.y * (area.height.saturating_sub(1)) as f32;
-        // draw the top character of the paddle by taking the fractional part of the top position
-        let top_char = TOP_BARS[(y.fract() * 8.0).round() as usize];
-        let bottom_char = BOTTOM_BARS[(y.fract() * 8.0).round() as usize];
-        let pos = self.pos.to_screen(area);
-        let ball_area = Rect::new(pos.x, pos.y, 1, 1);
-        Span::raw(top_char).render(ball_area, buf);
-        let ball_area = Rect::new(pos.x, pos.y + 1, 1, 1);
-        Span::raw(bottom_char).render(ball_area, buf);
-
-        // let debug = format!("{:3}, {:3}, {:5.2}, {:5.2}", pos.x, pos.y, x, y);
-        // let last_row = area.rows().last().unwrap();
-        // Line::from(debug).centered().render(last_row, buf);
+        let Some(resolution) = self.glyphs.resolution() else {
+            if self.glyphs == BallGlyphs::Ascii {
+                let screen = pos.to_screen(area);
+                buf.set_string(screen.x, screen.y, "o", self.style);
+                return;
+            }
+            return render_eighths(pos, area, buf, self.style);
+        };
+        let mut cells = SubCells::new(resolution, area);
+        let (cell_width, cell_height) = resolution.cell_size();
+        let height_in_cells = Ball::WIDTH / self.cell_aspect;
+        let width = (Ball::WIDTH * f32::from(cell_width)).round().max(1.0) as u32;
+        let height = (height_in_cells * f32::from(cell_height)).round().max(1.0) as u32;
+        let (max_x, max_y) = cells.size();
+        // position the top left pixel so the ball stays within the area at the edges
+        let x = (pos.x * max_x.saturating_sub(width) as f32).round() as u32;
+        let y = (pos.y * max_y.saturating_sub(height) as f32).round() as u32;
+        cells.fill(x, y, width, height);
+        cells.render(buf, self.style);
+    }
+}
+
+impl Widget for &Ball {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.widget(BallGlyphs::default()).render(area, buf);
+    }
+}
+
+/// Draw the ball a whole cell wide, using eighth-cell block characters for the vertical position.
+fn render_eighths(pos: Point, area: Rect, buf: &mut Buffer, style: Style) {
+    // use block characters that represent 1/8th of a cell to draw the ball
+    const TOP_BARS: [&str; 9] = ["█", "▇", "▆", "▅", "▄", "▃", "▂", "▁", " "];
+    const BOTTOM_BARS: [&str; 9] = [" ", "▔", "🮂", "🮃", "▀", "🮄", "🮅", "🮆", "█"];
+    let y = pos.y * (area.height.saturating_sub(1)) as f32;
+    // draw the top character of the ball by taking the fractional part of the top position
+    let top_char = TOP_BARS[(y.fract() * 8.0).round() as usize];
+    let bottom_char = BOTTOM_BARS[(y.fract() * 8.0).round() as usize];
+    let pos = pos.to_screen(area);
+    let ball_area = Rect::new(pos.x, pos.y, 1, 1);
+    Span::styled(top_char, style).render(ball_area, buf);
+    let ball_area = Rect::new(pos.x, pos.y + 1, 1, 1).intersection(area);
+    Span::styled(bottom_char, style).render(ball_area, buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    fn update(ball: &mut Ball, paddles: &[Paddle], level: &Level, physics: Physics) -> Option<Hit> {
+        let paddles: Vec<_> = paddles.iter().collect();
+        ball.update(STEP, &paddles, level, physics)
+    }
+
+    fn ball(x: f32, y: f32, vel_x: f32, vel_y: f32) -> Ball {
+        Ball {
+            pos: Point::new(x, y),
+            ..Ball::with_velocity(Velocity::new(vel_x, vel_y))
+        }
+    }
+
+    #[test]
+    fn top_and_bottom_paddles() {
+        let paddles = [Paddle::new(Side::Top), Paddle::new(Side::Bottom)];
+
+        let mut ball = ball(0.5, 0.01, 0.0, -0.5);
+        assert_eq!(
+            update(&mut ball, &paddles, &Level::open(), Physics::Classic),
+            Some(Hit::Paddle(0))
+        );
+        assert!(ball.vel.y > 0.0);
+        assert_eq!(ball.vel.x, 0.0);
+        assert!((0.0..=1.0).contains(&ball.pos.y));
+
+        // hitting off center sends the ball sideways
+        let mut ball = self::ball(0.55, 0.99, 0.0, 0.5);
+        assert_eq!(
+            update(&mut ball, &paddles, &Level::open(), Physics::Classic),
+            Some(Hit::Paddle(1))
+        );
+        assert!(ball.vel.y < 0.0);
+        assert_eq!(ball.vel.x, Velocity::VALID_Y[5]);
+    }
+
+    #[test]
+    fn corner_exit() {
+        let paddles = [Paddle::new(Side::Left), Paddle::new(Side::Top)];
+        let mut ball = ball(0.01, 0.01, -0.5, -0.5);
+        update(&mut ball, &paddles, &Level::open(), Physics::Classic);
+        // past the end of the left goal, so it bounces off the corner and out of the top goal
+        assert!(ball.pos.x >= 0.0);
+        assert_eq!(ball.exit_side(), Some(Side::Top));
+    }
+
+    #[test]
+    fn eliminated_side_is_a_wall() {
+        let mut ball = ball(0.99, 0.2, 0.5, 0.0);
+        // the right side's player is out, so only the left paddle is passed in
+        assert_eq!(
+            update(
+                &mut ball,
+                &[Paddle::new(Side::Left)],
+                &Level::open(),
+                Physics::Classic
+            ),
+            Some(Hit::Wall)
+        );
+        assert!(ball.vel.x < 0.0);
+        assert_eq!(ball.exit_side(), None);
+    }
+
+    #[test]
+    fn past_a_paddle_in_the_other_lane() {
+        // the top half's paddle can't leave its lane to stop a ball in the bottom half
+        let mut paddle = Paddle::new(Side::Left).with_lane(0.0, 0.5);
+        paddle.pos.y = 0.5 - paddle.length / 2.0;
+        let mut ball = ball(0.01, 0.6, -0.5, 0.0);
+        assert_eq!(
+            update(&mut ball, &[paddle], &Level::open(), Physics::Classic),
+            None
+        );
+        assert_eq!(ball.exit_side(), Some(Side::Left));
+    }
+
+    #[test]
+    fn obstacle_reflection() {
+        let level = Level::pillars();
+        let pillar = level.obstacles[0].bounds;
+        // heading right into the left edge of the pillar
+        let mut ball = ball(pillar.x - 0.01, pillar.y + 0.1, 0.2, 0.0);
+        assert_eq!(
+            update(&mut ball, &[], &level, Physics::Classic),
+            Some(Hit::Wall)
+        );
+        assert!(ball.vel.x < 0.0);
+        assert!(ball.pos.x < pillar.x);
+
+        // coming down onto its top edge
+        let mut ball = self::ball(pillar.x + 0.02, pillar.y - 0.01, 0.0, 0.5);
+        assert_eq!(
+            update(&mut ball, &[], &level, Physics::Classic),
+            Some(Hit::Wall)
+        );
+        assert!(ball.vel.y < 0.0);
+        assert!(ball.pos.y < pillar.y);
+    }
+
+    #[test]
+    fn pushed_out_by_moving_obstacle() {
+        let mut level = Level::elevator();
+        let bounds = &mut level.obstacles[0].bounds;
+        // the obstacle has moved onto a ball that is standing still, nearest its bottom edge
+        bounds.y = 0.5 - bounds.height + 0.01;
+        let bounds = *bounds;
+        let mut ball = Ball {
+            spin: 0.5,
+            ..ball(bounds.x + bounds.width / 2.0, 0.5, 0.0, 0.1)
+        };
+        ball.pos.y -= 0.001;
+        assert_eq!(
+            update(&mut ball, &[], &level, Physics::Classic),
+            Some(Hit::Wall)
+        );
+        assert_eq!(ball.pos.y, bounds.bottom());
+        assert!(ball.vel.y > 0.0);
+        // it was already heading out of the bottom, so it isn't reflected and keeps its spin
+        assert!(ball.spin > 0.0);
+    }
+
+    #[test]
+    fn portal_entered_once() {
+        let level = Level::portals();
+        let (entry, exit) = (level.portals[0].entry, level.portals[0].exit);
+        let mut ball = ball(entry.x - 0.035, entry.y, 0.1, 0.0);
+        update(&mut ball, &[], &level, Physics::Classic);
+        assert!((ball.pos.y - exit.y).abs() < 1e-6);
+
+        // the exit is another portal's entry, which doesn't send it straight back
+        update(&mut ball, &[], &level, Physics::Classic);
+        assert!(level.portals[1].is_at_entry(ball.pos));
+        assert!((ball.pos.y - exit.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn modern_bounce() {
+        let mut paddle = Paddle::new(Side::Right);
+        let mut ball = ball(0.99, 0.5 + paddle.length / 4.0, 0.5, 0.0);
+        assert_eq!(
+            update(
+                &mut ball,
+                &[paddle.clone()],
+                &Level::open(),
+                Physics::Modern
+            ),
+            Some(Hit::Paddle(0))
+        );
+        assert_eq!(ball.vel.x, -0.5);
+        // half way to the end of the paddle, with a still paddle
+        assert!((ball.vel.y - Ball::MAX_ALONG / 2.0).abs() < 1e-6);
+        assert_eq!(ball.spin, 0.0);
+
+        // the paddle's movement is passed on, and puts spin on the ball
+        paddle.vel = -0.4;
+        let mut ball = self::ball(0.99, 0.5, 0.5, 0.0);
+        update(
+            &mut ball,
+            &[paddle.clone()],
+            &Level::open(),
+            Physics::Modern,
+        );
+        assert!((ball.vel.y + 0.4 * Ball::VELOCITY_TRANSFER).abs() < 1e-6);
+        assert!((ball.spin - 0.4 * Ball::SPIN_PER_SPEED).abs() < 1e-6);
+    }
+
+    #[test]
+    fn spin_decay() {
+        let mut ball = Ball {
+            spin: Ball::MAX_SPIN,
+            ..ball(0.2, 0.5, 0.3, 0.0)
+        };
+        for _ in 0..10 {
+            update(&mut ball, &[], &Level::open(), Physics::Modern);
+        }
+        let expected = Ball::MAX_SPIN * (-Ball::SPIN_DECAY).exp();
+        assert!((ball.spin - expected).abs() < 1e-4);
+        // spin turns the ball clockwise without changing its speed
+        assert!(ball.vel.y > 0.0);
+        assert!((ball.vel.x.hypot(ball.vel.y) - 0.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn spin_reflection() {
+        let mut ball = Ball {
+            spin: 0.5,
+            ..ball(0.5, 0.01, 0.0, -0.5)
+        };
+        assert_eq!(
+            update(&mut ball, &[], &Level::open(), Physics::Modern),
+            Some(Hit::Wall)
+        );
+        assert!(ball.vel.y > 0.0);
+        assert!(ball.spin < 0.0);
+    }
+
+    #[test]
+    fn steep_return_stays_on_course() {
+        // moving the same way as a steep return would curve it back past straight up and down
+        let mut paddle = Paddle::new(Side::Right);
+        paddle.vel = Paddle::SPEED;
+        let mut ball = ball(0.99, 0.5 + paddle.length * 0.45, 0.26, 0.0);
+        update(
+            &mut ball,
+            &[paddle.clone()],
+            &Level::open(),
+            Physics::Modern,
+        );
+        assert_eq!(ball.vel.y, Ball::MAX_ALONG);
+        assert_eq!(ball.spin, 0.0);
+        // the angle from straight across the court, positive downwards
+        let heading = |ball: &Ball| ball.vel.y.atan2(-ball.vel.x);
+        let steepest = heading(&ball);
+        for _ in 0..30 {
+            update(&mut ball, &[], &Level::open(), Physics::Modern);
+            assert!(ball.vel.x <= -0.25);
+            // bouncing off the top and bottom only changes which way it heads
+            assert!(heading(&ball).abs() <= steepest + 1e-6);
+        }
+
+        // but the spin can still make it curve back, past straight across
+        paddle.vel = -Paddle::SPEED;
+        let mut ball = self::ball(0.99, 0.5 + paddle.length * 0.45, 0.26, 0.0);
+        update(
+            &mut ball,
+            &[paddle.clone()],
+            &Level::open(),
+            Physics::Modern,
+        );
+        assert!(heading(&ball) > 0.0);
+        for _ in 0..30 {
+            update(&mut ball, &[], &Level::open(), Physics::Modern);
+        }
+        assert!(heading(&ball) < 0.0);
+        assert!(ball.vel.x < 0.0);
+    }
+
+    #[test]
+    fn obstacle_spin_reflection() {
+        let level = Level::pillars();
+        let pillar = level.obstacles[0].bounds;
+        let mut ball = Ball {
+            spin: 0.5,
+            ..ball(pillar.x - 0.01, pillar.y + 0.1, 0.2, 0.0)
+        };
+        assert_eq!(
+            update(&mut ball, &[], &level, Physics::Modern),
+            Some(Hit::Wall)
+        );
+        assert!(ball.vel.x < 0.0);
+        assert!(ball.spin < 0.0);
+    }
+
+    #[test]
+    fn square_in_sub_cells() {
+        let ball = Ball {
+            pos: Point::new(0.0, 0.0),
+            ..Ball::with_velocity(Velocity::new(0.0, 0.0))
+        };
+        let area = Rect::new(0, 0, 4, 4);
+        // the ball is a cell wide, and as many braille dots tall as it takes to look square
+        for (cell_aspect, glyph) in [(1.0, "⣿"), (DEFAULT_CELL_ASPECT, "⠛"), (4.0, "⠉")] {
+            let mut buf = Buffer::empty(area);
+            ball.widget(BallGlyphs::Braille)
+                .cell_aspect(cell_aspect)
+                .render(area, &mut buf);
+            assert_eq!(buf[(0, 0)].symbol(), glyph, "cell aspect {cell_aspect}");
+            assert_eq!(buf[(1, 0)].symbol(), " ");
+            assert_eq!(buf[(0, 1)].symbol(), " ");
+        }
     }
 }
@@ -0,0 +1,361 @@
+use ratatui::{style::Color, symbols::border};
+
+use crate::{ball::BallGlyphs, graphics::GraphicsProtocol};
+
+/// The characters a client's terminal (and font) can be expected to draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GlyphSupport {
+    /// Only printable ASCII.
+    Ascii,
+    /// Box drawing and block elements, which almost all Unicode fonts include.
+    Blocks,
+    /// Symbols for Legacy Computing (`🮂`, `🬀`), which terminals that draw block characters
+    /// themselves support regardless of the font.
+    Legacy,
+}
+
+/// The colors a client's terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+/// What a client's terminal can display, used by all widgets when drawing for that client.
+///
+/// The initial profile is a guess based on the `TERM` the client sends with its pty request. It is
+/// refined when the client sends `COLORTERM` or when the terminal answers an XTVERSION query with
+/// its name. Graphics support is only known once the terminal answers the graphics queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub glyphs: GlyphSupport,
+    pub colors: ColorSupport,
+    pub graphics: Option<GraphicsProtocol>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            glyphs: GlyphSupport::Blocks,
+            colors: ColorSupport::Ansi16,
+            graphics: None,
+        }
+    }
+}
+
+impl Capabilities {
+    // Terminals known to draw legacy computing glyphs and support 24-bit color
+    const MODERN_TERMINALS: [&'static str; 5] = ["kitty", "wezterm", "foot", "ghostty", "contour"];
+
+    /// Guess the capabilities from the terminal type.
+    pub fn from_term(term: &str) -> Self {
+        let term = term.to_ascii_lowercase();
+        if term.is_empty() || term == "dumb" || term.starts_with("vt") {
+            return Self {
+                glyphs: GlyphSupport::Ascii,
+                ..Self::default()
+            };
+        }
+        if Self::MODERN_TERMINALS
+            .iter()
+            .any(|name| term.contains(name))
+        {
+            return Self {
+                glyphs: GlyphSupport::Legacy,
+                colors: ColorSupport::TrueColor,
+                graphics: None,
+            };
+        }
+        let colors = if term.ends_with("-direct") || term.ends_with("-truecolor") {
+            ColorSupport::TrueColor
+        } else if term.ends_with("256color") {
+            ColorSupport::Indexed256
+        } else {
+            ColorSupport::Ansi16
+        };
+        Self {
+            colors,
+            ..Self::default()
+        }
+    }
+
+    /// Update the capabilities from an environment variable sent by the client.
+    pub fn apply_env(&mut self, name: &str, value: &str) {
+        if name == "COLORTERM" && matches!(value, "truecolor" | "24bit") {
+            self.colors = ColorSupport::TrueColor;
+        }
+    }
+
+    /// Update the capabilities from the terminal's reply to an XTVERSION query, e.g.
+    /// `kitty(0.35.2)` or `WezTerm 20240203`.
+    pub fn apply_terminal_version(&mut self, version: &str) {
+        let version = version.to_ascii_lowercase();
+        if Self::MODERN_TERMINALS
+            .iter()
+            .any(|name| version.starts_with(name))
+        {
+            self.glyphs = self.glyphs.max(GlyphSupport::Legacy);
+            self.colors = ColorSupport::TrueColor;
+        } else if version.starts_with("iterm2") {
+            self.colors = ColorSupport::TrueColor;
+        }
+    }
+
+    /// Update the capabilities from the terminal's reply to a device attributes query.
+    pub fn apply_device_attributes(&mut self, attributes: &[u16]) {
+        // kitty graphics are preferred, as they need much less bandwidth
+        if attributes.contains(&4) && self.graphics.is_none() {
+            self.graphics = Some(GraphicsProtocol::Sixel);
+        }
+    }
+
+    /// Update the capabilities from the terminal's reply to a kitty graphics query.
+    pub fn apply_kitty_graphics(&mut self, supported: bool) {
+        if supported {
+            self.graphics = Some(GraphicsProtocol::Kitty);
+        }
+    }
+
+    /// The ball glyphs to use given the player's preference.
+    pub fn ball_glyphs(&self, preferred: BallGlyphs) -> BallGlyphs {
+        match (self.glyphs, preferred) {
+            (GlyphSupport::Ascii, _) => BallGlyphs::Ascii,
+            (GlyphSupport::Blocks, BallGlyphs::Eighths | BallGlyphs::Sextants) => {
+                BallGlyphs::Quadrants
+            }
+            (_, preferred) => preferred,
+        }
+    }
+
+    /// The border characters to use for blocks.
+    pub fn border_set(&self) -> border::Set {
+        match self.glyphs {
+            GlyphSupport::Ascii => border::Set {
+                top_left: "+",
+                top_right: "+",
+                bottom_left: "+",
+                bottom_right: "+",
+                vertical_left: "|",
+                vertical_right: "|",
+                horizontal_top: "-",
+                horizontal_bottom: "-",
+            },
+            _ => border::PLAIN,
+        }
+    }
+
+    /// The given text, or the ASCII text in its place if the terminal only draws ASCII.
+    pub const fn text(&self, unicode: &'static str, ascii: &'static str) -> &'static str {
+        match self.glyphs {
+            GlyphSupport::Ascii => ascii,
+            _ => unicode,
+        }
+    }
+
+    /// Convert a color to the closest one the terminal can display.
+    pub fn color(&self, color: Color) -> Color {
+        match (self.colors, color) {
+            (ColorSupport::TrueColor, color) => color,
+            (ColorSupport::Indexed256, Color::Rgb(r, g, b)) => {
+                Color::Indexed(rgb_to_indexed(r, g, b))
+            }
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => rgb_to_ansi(r, g, b),
+            (ColorSupport::Ansi16, Color::Indexed(index)) => {
+                let (r, g, b) = indexed_to_rgb(index);
+                rgb_to_ansi(r, g, b)
+            }
+            (_, color) => color,
+        }
+    }
+}
+
+/// The RGB value of a color, for drawing it with graphics. The terminal's default colors are
+/// assumed to be black.
+pub fn color_to_rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) => indexed_to_rgb(index),
+        color => ANSI_COLORS
+            .iter()
+            .find(|(ansi, _)| *ansi == color)
+            .map_or((0, 0, 0), |(_, rgb)| *rgb),
+    }
+}
+
+// The RGB values of the 16 ANSI colors, using the xterm defaults
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).unsigned_abs().pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn rgb_to_ansi(r: u8, g: u8, b: u8) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map_or(Color::White, |(color, _)| *color)
+}
+
+// The levels of each component in the 6x6x6 color cube of the 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn rgb_to_indexed(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    // the grayscale ramp is closer for colors with little saturation
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray_step = (average.saturating_sub(8) / 10).min(23);
+    let gray = 232 + gray_step;
+    if distance(indexed_to_rgb(gray), (r, g, b)) < distance(indexed_to_rgb(cube), (r, g, b)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize].1,
+        16..=231 => {
+            let index = index - 16;
+            (
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_term() {
+        let capabilities = |term| {
+            let Capabilities { glyphs, colors, .. } = Capabilities::from_term(term);
+            (glyphs, colors)
+        };
+        use ColorSupport::*;
+        use GlyphSupport::*;
+        assert_eq!(capabilities(""), (Ascii, Ansi16));
+        assert_eq!(capabilities("dumb"), (Ascii, Ansi16));
+        assert_eq!(capabilities("vt100"), (Ascii, Ansi16));
+        assert_eq!(capabilities("xterm"), (Blocks, Ansi16));
+        assert_eq!(capabilities("xterm-256color"), (Blocks, Indexed256));
+        assert_eq!(capabilities("xterm-direct"), (Blocks, TrueColor));
+        assert_eq!(capabilities("xterm-kitty"), (Legacy, TrueColor));
+    }
+
+    #[test]
+    fn refined_by_client() {
+        let mut capabilities = Capabilities::from_term("xterm-256color");
+        capabilities.apply_env("LANG", "truecolor");
+        capabilities.apply_env("COLORTERM", "yes");
+        assert_eq!(capabilities.colors, ColorSupport::Indexed256);
+        capabilities.apply_env("COLORTERM", "truecolor");
+        assert_eq!(capabilities.colors, ColorSupport::TrueColor);
+
+        let mut capabilities = Capabilities::from_term("xterm");
+        capabilities.apply_terminal_version("tmux 3.4");
+        assert_eq!(capabilities, Capabilities::from_term("xterm"));
+        capabilities.apply_terminal_version("iTerm2 3.5.0");
+        assert_eq!(capabilities.glyphs, GlyphSupport::Blocks);
+        assert_eq!(capabilities.colors, ColorSupport::TrueColor);
+        capabilities.apply_terminal_version("WezTerm 20240203");
+        assert_eq!(capabilities.glyphs, GlyphSupport::Legacy);
+    }
+
+    #[test]
+    fn graphics_protocol() {
+        let mut capabilities = Capabilities::default();
+        capabilities.apply_device_attributes(&[62, 22]);
+        assert_eq!(capabilities.graphics, None);
+        capabilities.apply_device_attributes(&[62, 4, 22]);
+        assert_eq!(capabilities.graphics, Some(GraphicsProtocol::Sixel));
+
+        // kitty graphics are preferred whichever reply arrives first
+        capabilities.apply_kitty_graphics(true);
+        assert_eq!(capabilities.graphics, Some(GraphicsProtocol::Kitty));
+        capabilities.apply_device_attributes(&[62, 4, 22]);
+        assert_eq!(capabilities.graphics, Some(GraphicsProtocol::Kitty));
+    }
+
+    #[test]
+    fn color_downgrade() {
+        let with_colors = |colors| Capabilities {
+            colors,
+            ..Capabilities::default()
+        };
+        let true_color = with_colors(ColorSupport::TrueColor);
+        let indexed = with_colors(ColorSupport::Indexed256);
+        let ansi = with_colors(ColorSupport::Ansi16);
+
+        let red = Color::Rgb(255, 0, 0);
+        assert_eq!(true_color.color(red), red);
+        assert_eq!(indexed.color(red), Color::Indexed(196));
+        assert_eq!(ansi.color(red), Color::LightRed);
+        assert_eq!(ansi.color(Color::Indexed(196)), Color::LightRed);
+
+        // grays use the grayscale ramp
+        let gray = Color::Rgb(128, 128, 128);
+        assert_eq!(indexed.color(gray), Color::Indexed(244));
+        assert_eq!(ansi.color(gray), Color::DarkGray);
+
+        // named colors are left alone
+        assert_eq!(ansi.color(Color::Red), Color::Red);
+        assert_eq!(indexed.color(Color::Indexed(42)), Color::Indexed(42));
+    }
+
+    #[test]
+    fn ascii_overlays() {
+        use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+        use crate::{help::HelpOverlay, profile::Profile, settings::SettingsScreen};
+
+        let capabilities = Capabilities::from_term("vt100");
+        let area = Rect::new(0, 0, 80, 30);
+        let profile = Profile::default();
+        let is_ascii = |buf: &Buffer| buf.content().iter().all(|cell| cell.symbol().is_ascii());
+
+        let mut buf = Buffer::empty(area);
+        HelpOverlay::new(&profile.keymap, capabilities).render(area, &mut buf);
+        assert!(is_ascii(&buf));
+        let corner = HelpOverlay::area(area);
+        assert_eq!(buf[(corner.x, corner.y)].symbol(), "+");
+
+        let mut buf = Buffer::empty(area);
+        SettingsScreen::default()
+            .widget(&profile, capabilities)
+            .render(area, &mut buf);
+        assert!(is_ascii(&buf));
+    }
+}
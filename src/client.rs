@@ -0,0 +1,296 @@
+use std::{io, sync::Arc, time::Instant};
+
+use ratatui::prelude::*;
+use tracing::warn;
+
+use crate::{
+    capabilities::Capabilities,
+    effects::Effects,
+    event::GameEvent,
+    game::Game,
+    graphics::GraphicsRenderer,
+    help::HelpOverlay,
+    keymap::{key_name, Action},
+    latency::LatencyProbe,
+    physics::DEFAULT_CELL_ASPECT,
+    profile::Profile,
+    server::SshTerminal,
+    settings::SettingsScreen,
+    sound,
+    theme::Themes,
+    tournament::Tournament,
+    viewer::{Notifications, Role, Viewer},
+};
+
+/// A connected player's terminal and per-player state.
+#[derive(Debug)]
+pub struct Client {
+    pub client_id: usize,
+    pub terminal: SshTerminal,
+    pub profile: Profile,
+    /// The settings screen, if the player has it open.
+    pub settings: Option<SettingsScreen>,
+    pub show_help: bool,
+    pub notifications: Notifications,
+    pub capabilities: Capabilities,
+    pub themes: Arc<Themes>,
+    /// Draws the ball and paddles as images while the graphics renderer is in use.
+    graphics: Option<GraphicsRenderer>,
+    pub effects: Effects,
+    pub latency: LatencyProbe,
+}
+
+impl Client {
+    pub fn new(
+        client_id: usize,
+        terminal: SshTerminal,
+        profile: Profile,
+        capabilities: Capabilities,
+        themes: Arc<Themes>,
+    ) -> Self {
+        Self {
+            client_id,
+            terminal,
+            profile,
+            settings: None,
+            show_help: false,
+            notifications: Notifications::default(),
+            capabilities,
+            themes,
+            graphics: None,
+            effects: Effects::default(),
+            latency: LatencyProbe::default(),
+        }
+    }
+
+    /// React to something that happened in the game, with effects, sounds and notifications.
+    pub fn handle_event(&mut self, event: &GameEvent, game: &Game) -> io::Result<()> {
+        self.effects.handle_event(event);
+        let preferences = &self.profile.preferences;
+        let mut output = String::new();
+        if sound::rings_bell(event, preferences) {
+            output.push_str(sound::BELL);
+        }
+        match *event {
+            GameEvent::MatchEnded { winner, ref score } => {
+                let name = game.team_name(winner);
+                let message = if game.config().mode.lives().is_some() {
+                    format!("{name} won the match")
+                } else {
+                    let won = score[winner];
+                    let lost = (0..score.len())
+                        .filter(|&team| team != winner)
+                        .map(|team| score[team])
+                        .max()
+                        .unwrap_or_default();
+                    format!("{name} won the match {won}-{lost}")
+                };
+                if preferences.desktop_notifications && !preferences.muted {
+                    output.push_str(&sound::notification(&message));
+                }
+                self.notifications.push(message);
+            }
+            GameEvent::PowerUpCollected { slot, kind } => {
+                let name = game.player_name(slot);
+                self.notifications.push(kind.message(&name));
+            }
+            GameEvent::RunEnded { slot, returns }
+                if game.role(self.client_id) == Role::Player(slot)
+                    && returns > self.profile.practice_best =>
+            {
+                self.profile.practice_best = returns;
+                // saved in the background, as this runs between frames with the game locked
+                let (client_id, profile) = (self.client_id, self.profile.clone());
+                tokio::task::spawn_blocking(move || {
+                    if let Err(err) = profile.save() {
+                        warn!(client_id, "Failed to save profile: {err}");
+                    }
+                });
+                self.notifications
+                    .push(format!("New personal best: {returns} returns"));
+            }
+            GameEvent::PlayerEliminated { team } => {
+                let name = game.team_name(team);
+                self.notifications.push(format!("{name} is out"));
+            }
+            GameEvent::PlayerJoined {
+                client_id,
+                ref name,
+                ..
+            } if client_id != self.client_id => {
+                self.notifications.push(format!("{name} joined the game"));
+            }
+            GameEvent::PlayerLeft { ref name, .. } => {
+                self.notifications.push(format!("{name} left the game"));
+            }
+            _ => {}
+        }
+        self.terminal.backend_mut().write_raw(&output)
+    }
+
+    /// Ask the terminal for its status when it is time to measure the latency again.
+    pub fn probe_latency(&mut self) -> io::Result<()> {
+        if self.latency.send(Instant::now()) {
+            self.terminal.backend_mut().query_status()?;
+        }
+        Ok(())
+    }
+
+    /// The height of the terminal's cells relative to their width.
+    pub fn cell_aspect(&self) -> f32 {
+        self.terminal
+            .backend()
+            .cell_aspect()
+            .unwrap_or(DEFAULT_CELL_ASPECT)
+    }
+
+    /// Whether the ball and paddles can be drawn with graphics.
+    fn can_use_graphics(&self) -> bool {
+        self.profile.preferences.graphics && self.capabilities.graphics.is_some()
+    }
+
+    pub fn draw(&mut self, game: &Game) -> color_eyre::Result<()> {
+        self.notifications.expire();
+        self.effects
+            .record_balls(game.balls().iter().map(|ball| ball.pos));
+        let can_use_graphics = self.can_use_graphics();
+        let cell_size = self.terminal.backend().cell_size();
+        let area = Rect::from((Position::ORIGIN, self.terminal.size()?));
+        let mut viewer = Viewer {
+            role: game.role(self.client_id),
+            preferences: &self.profile.preferences,
+            capabilities: self.capabilities,
+            theme: self.themes.get(&self.profile.preferences.theme),
+            cell_aspect: self.cell_aspect(),
+            graphics: false,
+            effects: &self.effects,
+        };
+        let scene = cell_size
+            .filter(|_| can_use_graphics)
+            .and_then(|cell_size| game.view(&viewer).scene(area, cell_size));
+        viewer.graphics = scene.is_some();
+        if scene.is_none() {
+            clear_graphics(&mut self.graphics, &mut self.terminal)?;
+        }
+
+        let overlays = Overlays {
+            notifications: &self.notifications,
+            profile: &self.profile,
+            capabilities: self.capabilities,
+            show_help: self.show_help,
+            settings: self.settings.as_ref(),
+        };
+        let mut covered = Vec::new();
+        self.terminal.draw(|frame| {
+            let area = frame.area();
+            frame.render_widget(game.view(&viewer), area);
+            let court = Game::court_area(area, viewer.cell_aspect);
+            let hint = !Game::is_too_small(area, viewer.cell_aspect);
+            covered = overlays.render(frame, court, hint);
+        })?;
+
+        if let (Some(mut scene), Some(protocol)) = (scene, self.capabilities.graphics) {
+            // graphics are drawn over everything else, so nothing is drawn under the overlays
+            scene.covered = covered;
+            let graphics = self
+                .graphics
+                .get_or_insert_with(|| GraphicsRenderer::new(protocol));
+            self.terminal
+                .backend_mut()
+                .write_raw(&graphics.draw(scene))?;
+        }
+        Ok(())
+    }
+
+    /// Draw the bracket of a tournament the player is waiting in between matches.
+    pub fn draw_bracket(&mut self, tournament: &Tournament) -> color_eyre::Result<()> {
+        self.notifications.expire();
+        clear_graphics(&mut self.graphics, &mut self.terminal)?;
+        let viewer = Viewer {
+            role: Role::Spectator,
+            preferences: &self.profile.preferences,
+            capabilities: self.capabilities,
+            theme: self.themes.get(&self.profile.preferences.theme),
+            cell_aspect: self.cell_aspect(),
+            graphics: false,
+            effects: &self.effects,
+        };
+        let own = tournament.seed(&self.profile.identity);
+        let overlays = Overlays {
+            notifications: &self.notifications,
+            profile: &self.profile,
+            capabilities: self.capabilities,
+            show_help: self.show_help,
+            settings: self.settings.as_ref(),
+        };
+        self.terminal.draw(|frame| {
+            let area = frame.area();
+            frame.render_widget(tournament.view(&viewer, own), area);
+            overlays.render(frame, area, true);
+        })?;
+        Ok(())
+    }
+}
+
+/// Stop drawing with graphics, removing any images from the terminal.
+fn clear_graphics(
+    graphics: &mut Option<GraphicsRenderer>,
+    terminal: &mut SshTerminal,
+) -> io::Result<()> {
+    if let Some(mut graphics) = graphics.take() {
+        // redraw every cell to remove the images
+        terminal.backend_mut().write_raw(&graphics.clear())?;
+        terminal.clear()?;
+    }
+    Ok(())
+}
+
+/// What a client shows over the court or bracket.
+struct Overlays<'a> {
+    notifications: &'a Notifications,
+    profile: &'a Profile,
+    capabilities: Capabilities,
+    show_help: bool,
+    settings: Option<&'a SettingsScreen>,
+}
+
+impl Overlays<'_> {
+    /// Draw the notifications and help hint over the given court or bracket, and the help and
+    /// settings screens over the whole frame, returning the areas they cover.
+    fn render(&self, frame: &mut Frame, court: Rect, hint: bool) -> Vec<Rect> {
+        let Self {
+            notifications,
+            profile,
+            capabilities,
+            show_help,
+            settings,
+        } = *self;
+        let area = frame.area();
+        frame.render_widget(notifications.widget(capabilities), court);
+        let mut covered = notifications.areas(court);
+        let keymap = &profile.keymap;
+        let help_key = keymap.keys(Action::Help).first();
+        if let Some(key) = help_key.filter(|_| hint) {
+            // drawn over the bottom border, inside the corner
+            let hint = Rect::new(
+                court.x + 1,
+                court.bottom().saturating_sub(1),
+                court.width,
+                1,
+            )
+            .intersection(court.inner(Margin::new(1, 0)));
+            let hint_text = Line::from(format!(" {} help ", key_name(*key))).right_aligned();
+            frame.render_widget(hint_text, hint);
+            covered.push(hint);
+        }
+        if show_help {
+            frame.render_widget(HelpOverlay::new(keymap, capabilities), area);
+            covered.push(HelpOverlay::area(area));
+        }
+        if let Some(settings) = settings {
+            frame.render_widget(settings.widget(profile, capabilities), area);
+            covered.push(SettingsScreen::area(area));
+        }
+        covered
+    }
+}
@@ -0,0 +1,269 @@
+use ratatui::prelude::*;
+
+use crate::{
+    capabilities::GlyphSupport,
+    graphics::{Rgb, Shape},
+};
+
+/// How each cell of a court marking is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    /// The whole cell, as used by the score digits.
+    Block,
+    /// A thin vertical line through the middle of the cell.
+    Line,
+    /// A small dot in the middle of the cell.
+    Dot,
+}
+
+impl Fill {
+    fn symbol(self, glyphs: GlyphSupport) -> &'static str {
+        match (self, glyphs) {
+            (Fill::Block, GlyphSupport::Ascii) => "#",
+            (Fill::Block, _) => "█",
+            (Fill::Line, GlyphSupport::Ascii) => "|",
+            (Fill::Line, _) => "│",
+            (Fill::Dot, GlyphSupport::Ascii) => ".",
+            (Fill::Dot, _) => "·",
+        }
+    }
+}
+
+/// The cells covered by a court marking, which can be drawn with characters or turned into shapes
+/// for the graphics renderer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marks {
+    pub fill: Fill,
+    pub cells: Vec<Rect>,
+}
+
+impl Marks {
+    pub fn render(&self, buf: &mut Buffer, glyphs: GlyphSupport, style: Style) {
+        let symbol = self.fill.symbol(glyphs);
+        for rect in &self.cells {
+            for position in rect.intersection(buf.area).positions() {
+                if let Some(cell) = buf.cell_mut(position) {
+                    cell.set_symbol(symbol).set_style(style);
+                }
+            }
+        }
+    }
+
+    /// The marks as shapes in a scene covering `area`, with cells of the given size in pixels.
+    pub fn shapes(&self, area: Rect, cell_size: Size, color: Rgb) -> Vec<Shape> {
+        let (cell_width, cell_height) = (u32::from(cell_size.width), u32::from(cell_size.height));
+        self.cells
+            .iter()
+            .map(|rect| rect.intersection(area))
+            .filter(|rect| !rect.is_empty())
+            .map(|rect| {
+                let x = u32::from(rect.x - area.x) * cell_width;
+                let y = u32::from(rect.y - area.y) * cell_height;
+                let width = u32::from(rect.width) * cell_width;
+                let height = u32::from(rect.height) * cell_height;
+                let (x, y, width, height) = match self.fill {
+                    Fill::Block => (x, y, width, height),
+                    Fill::Line => {
+                        let thickness = (cell_width / 6).max(1);
+                        (x + (cell_width - thickness) / 2, y, thickness, height)
+                    }
+                    Fill::Dot => {
+                        let size = (cell_width / 4).max(1);
+                        (
+                            x + (cell_width - size) / 2,
+                            y + (cell_height - size) / 2,
+                            size,
+                            size,
+                        )
+                    }
+                };
+                Shape {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The dashed net down the middle of the court.
+///
+/// The dashes get longer on taller courts so the net keeps the same look.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Net;
+
+impl Net {
+    pub fn marks(&self, area: Rect) -> Marks {
+        let dash = (area.height / 15).max(1);
+        let x = area.x + area.width / 2;
+        let cells = (area.top()..area.bottom())
+            .step_by(usize::from(dash) * 2)
+            .map(|y| Rect::new(x, y, 1, dash).intersection(area))
+            .collect();
+        Marks {
+            fill: Fill::Line,
+            cells,
+        }
+    }
+}
+
+impl Widget for Net {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.marks(area)
+            .render(buf, GlyphSupport::Blocks, Style::default());
+    }
+}
+
+/// Dotted lines a quarter of the way in from each side, which players can turn on in their
+/// settings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CourtMarkings;
+
+impl CourtMarkings {
+    pub fn marks(&self, area: Rect) -> Marks {
+        let columns = [area.x + area.width / 4, area.x + area.width * 3 / 4];
+        let cells = columns
+            .into_iter()
+            .flat_map(|x| {
+                (area.top()..area.bottom())
+                    .step_by(2)
+                    .map(move |y| Rect::new(x, y, 1, 1))
+            })
+            .collect();
+        Marks {
+            fill: Fill::Dot,
+            cells,
+        }
+    }
+}
+
+impl Widget for CourtMarkings {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.marks(area)
+            .render(buf, GlyphSupport::Blocks, Style::default());
+    }
+}
+
+/// A number drawn in large seven-segment digits, such as a player's score.
+#[derive(Debug, Clone, Copy)]
+pub struct BigNumber {
+    value: u32,
+    height: u16,
+}
+
+impl BigNumber {
+    // The segments lit for each digit, as bits for segments a to g: top, top right, bottom right,
+    // bottom, bottom left, top left and middle.
+    const SEGMENTS: [u8; 10] = [
+        0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+        0b1111111, 0b1101111,
+    ];
+
+    pub const fn new(value: u32, height: u16) -> Self {
+        Self { value, height }
+    }
+
+    /// The height of the digits for a court of the given height, which is always odd so that the
+    /// middle segment is centered.
+    pub fn scaled_height(court_height: u16) -> u16 {
+        let height = (court_height / 5).clamp(5, 9);
+        height - (1 - height % 2)
+    }
+
+    /// The size of each digit and the gap between them, in cells.
+    fn digit_size(&self) -> (u16, u16) {
+        // cells are about twice as tall as they are wide, so this looks roughly 2:3
+        let width = self.height + 1;
+        (width, width / 4 + 1)
+    }
+
+    /// The width of the whole number in cells.
+    pub fn width(&self) -> u16 {
+        let (width, gap) = self.digit_size();
+        let digits = self.value.to_string().len() as u16;
+        digits * width + (digits - 1) * gap
+    }
+
+    /// The cells lit by the number, centered horizontally at the top of the area.
+    pub fn marks(&self, area: Rect) -> Marks {
+        let (width, gap) = self.digit_size();
+        let height = self.height;
+        let thickness = (width / 3).max(1);
+        let middle = height / 2;
+        let mut x = area.x + area.width.saturating_sub(self.width()) / 2;
+        let mut cells = Vec::new();
+        for digit in self.value.to_string().bytes() {
+            let segments = Self::SEGMENTS[usize::from(digit - b'0')];
+            let rects = [
+                Rect::new(x, area.y, width, 1),
+                Rect::new(x + width - thickness, area.y, thickness, middle + 1),
+                Rect::new(
+                    x + width - thickness,
+                    area.y + middle,
+                    thickness,
+                    height - middle,
+                ),
+                Rect::new(x, area.y + height - 1, width, 1),
+                Rect::new(x, area.y + middle, thickness, height - middle),
+                Rect::new(x, area.y, thickness, middle + 1),
+                Rect::new(x, area.y + middle, width, 1),
+            ];
+            cells.extend(
+                rects
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(segment, _)| segments & (1 << segment) != 0)
+                    .map(|(_, rect)| rect.intersection(area)),
+            );
+            x += width + gap;
+        }
+        Marks {
+            fill: Fill::Block,
+            cells,
+        }
+    }
+}
+
+impl Widget for BigNumber {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.marks(area)
+            .render(buf, GlyphSupport::Blocks, Style::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(number: BigNumber) -> Vec<String> {
+        let area = Rect::new(0, 0, number.width(), number.height);
+        let mut buf = Buffer::empty(area);
+        number
+            .marks(area)
+            .render(&mut buf, GlyphSupport::Ascii, Style::default());
+        (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seven_segment_digits() {
+        assert_eq!(
+            draw(BigNumber::new(42, 5)),
+            [
+                "##  ##  ######",
+                "##  ##      ##",
+                "######  ######",
+                "    ##  ##    ",
+                "    ##  ######",
+            ]
+        );
+    }
+}
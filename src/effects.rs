@@ -0,0 +1,157 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{event::GameEvent, game::Game, physics::Point};
+
+/// The state of the visual effects shown to one client, driven by [`GameEvent`]s.
+///
+/// Effects are tracked for every client and only drawn if the player has turned them on in their
+/// preferences, so turning one on takes effect immediately.
+#[derive(Debug, Default)]
+pub struct Effects {
+    // Recent positions of every ball in game coordinates, oldest first
+    trail: VecDeque<(Point, Instant)>,
+    // When positions were last added to the trail
+    last_recorded: Option<Instant>,
+    // When each paddle last returned the ball
+    paddle_hits: [Option<Instant>; Game::MAX_PLAYERS],
+    // When the last point was scored
+    point_scored: Option<Instant>,
+}
+
+impl Effects {
+    const TRAIL_DURATION: Duration = Duration::from_millis(200);
+    // Positions closer together than this are skipped, so the trail stays evenly spaced
+    const TRAIL_INTERVAL: Duration = Duration::from_millis(30);
+    const FLASH_DURATION: Duration = Duration::from_millis(150);
+    const SHAKE_DURATION: Duration = Duration::from_millis(400);
+    const SHAKE_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn handle_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::PaddleHit { slot } => self.paddle_hits[slot] = Some(Instant::now()),
+            GameEvent::PointScored { .. }
+            | GameEvent::LifeLost { .. }
+            | GameEvent::RunEnded { .. } => {
+                self.point_scored = Some(Instant::now());
+                // the ball is served from the center, so don't draw a trail across the court
+                self.trail.clear();
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the balls' positions for the trail and forget positions that are too old.
+    pub fn record_balls(&mut self, positions: impl IntoIterator<Item = Point>) {
+        let now = Instant::now();
+        self.trail
+            .retain(|(_, time)| now.duration_since(*time) < Self::TRAIL_DURATION);
+        let due = self
+            .last_recorded
+            .is_none_or(|time| now.duration_since(time) >= Self::TRAIL_INTERVAL);
+        if due {
+            self.trail
+                .extend(positions.into_iter().map(|pos| (pos, now)));
+            self.last_recorded = Some(now);
+        }
+    }
+
+    /// The positions of the trails behind the balls with how faded each one is, from 0 (new) to 1
+    /// (about to disappear).
+    pub fn trail(&self) -> impl Iterator<Item = (Point, f32)> + '_ {
+        self.trail.iter().map(|(pos, time)| {
+            let age = time.elapsed().as_secs_f32() / Self::TRAIL_DURATION.as_secs_f32();
+            (*pos, age.min(1.0))
+        })
+    }
+
+    /// Whether the paddle in the given slot has just returned the ball.
+    pub fn paddle_flashing(&self, slot: usize) -> bool {
+        self.paddle_hits[slot].is_some_and(|time| time.elapsed() < Self::FLASH_DURATION)
+    }
+
+    /// Whether a point has just been scored, and the court is shaking.
+    pub fn shaking(&self) -> bool {
+        self.point_scored
+            .is_some_and(|time| time.elapsed() < Self::SHAKE_DURATION)
+    }
+
+    /// How far to move the court sideways while it is shaking, in cells.
+    pub fn shake_offset(&self) -> i32 {
+        match self.point_scored {
+            Some(time) if self.shaking() => {
+                let step = time.elapsed().as_millis() / Self::SHAKE_INTERVAL.as_millis();
+                if step % 2 == 0 {
+                    1
+                } else {
+                    -1
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ago(duration: Duration) -> Instant {
+        Instant::now().checked_sub(duration).unwrap()
+    }
+
+    #[test]
+    fn paddle_flash() {
+        let mut effects = Effects::default();
+        effects.handle_event(&GameEvent::PaddleHit { slot: 1 });
+        assert!(effects.paddle_flashing(1));
+        assert!(!effects.paddle_flashing(0));
+        effects.paddle_hits[1] = Some(ago(Effects::FLASH_DURATION));
+        assert!(!effects.paddle_flashing(1));
+    }
+
+    #[test]
+    fn score_shake() {
+        let mut effects = Effects::default();
+        effects.record_balls([Point::CENTER]);
+        effects.handle_event(&GameEvent::PointScored {
+            team: 0,
+            score: vec![1, 0],
+        });
+        assert!(effects.shaking());
+        assert_eq!(effects.shake_offset(), 1);
+        assert_eq!(effects.trail().count(), 0);
+
+        // the court moves back and forth
+        effects.point_scored = Some(ago(Effects::SHAKE_INTERVAL + Duration::from_millis(10)));
+        assert_eq!(effects.shake_offset(), -1);
+
+        effects.point_scored = Some(ago(Effects::SHAKE_DURATION));
+        assert!(!effects.shaking());
+        assert_eq!(effects.shake_offset(), 0);
+    }
+
+    #[test]
+    fn trail_timing() {
+        let mut effects = Effects::default();
+        effects.record_balls([Point::new(0.1, 0.5), Point::new(0.9, 0.5)]);
+        // too soon after the last positions
+        effects.record_balls([Point::new(0.2, 0.5), Point::new(0.8, 0.5)]);
+        assert_eq!(effects.trail().count(), 2);
+        assert!(effects.trail().all(|(_, age)| age < 0.5));
+
+        effects.last_recorded = Some(ago(Effects::TRAIL_INTERVAL));
+        effects.record_balls([Point::new(0.2, 0.5)]);
+        assert_eq!(effects.trail().count(), 3);
+
+        // old positions fade out and are forgotten
+        for (_, time) in &mut effects.trail {
+            *time = ago(Effects::TRAIL_DURATION);
+        }
+        assert!(effects.trail().all(|(_, age)| age == 1.0));
+        effects.record_balls([Point::new(0.3, 0.5)]);
+        assert_eq!(effects.trail().count(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info, warn};
+
+use crate::power_up::PowerUpKind;
+
+/// Something that happened in the game.
+///
+/// Every change worth reacting to is published as an event by the [`Game`](crate::game::Game),
+/// so stats, sounds, effects and anything else can be built on the same stream instead of
+/// watching the game state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// The paddle in the given slot returned the ball.
+    PaddleHit { slot: usize },
+    /// The ball bounced off a side of the court without a paddle.
+    WallBounce,
+    /// The given team won a point, making each team's score the given one.
+    PointScored { team: usize, score: Vec<u32> },
+    /// The given team let the ball past in a mode played with lives, leaving them with the given
+    /// number of lives.
+    LifeLost { team: usize, lives: u32 },
+    /// The player in the given slot let the ball past in practice, after returning it the given
+    /// number of times in a row.
+    RunEnded { slot: usize, returns: u32 },
+    /// The given team has run out of lives, and their side is now a wall.
+    PlayerEliminated { team: usize },
+    /// The ball collected a power-up on behalf of the player in the given slot.
+    PowerUpCollected { slot: usize, kind: PowerUpKind },
+    /// A power-up affecting the player in the given slot, or the whole game for `None`, ran out.
+    PowerUpExpired {
+        kind: PowerUpKind,
+        slot: Option<usize>,
+    },
+    /// The ball was put back in the center and will be served shortly.
+    Serve,
+    /// A player took the given slot.
+    PlayerJoined {
+        slot: usize,
+        client_id: usize,
+        name: String,
+    },
+    /// The player in the given slot left the game.
+    PlayerLeft {
+        slot: usize,
+        client_id: usize,
+        name: String,
+    },
+    /// The given team won the match with the given final scores, or lives left. The scores are
+    /// reset for the next match.
+    MatchEnded { winner: usize, score: Vec<u32> },
+}
+
+/// Publishes [`GameEvent`]s to any number of subscribers.
+///
+/// Each subscriber gets every event published after it subscribed. A subscriber that falls more
+/// than [`EventBus::CAPACITY`] events behind skips the oldest ones.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<GameEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub const CAPACITY: usize = 256;
+
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(Self::CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: GameEvent) {
+        // there being no subscribers isn't an error, the event just isn't needed
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Log every event, until the game is dropped.
+pub async fn log_events(mut events: broadcast::Receiver<GameEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event @ (GameEvent::PaddleHit { .. } | GameEvent::WallBounce)) => {
+                debug!(?event, "Game event");
+            }
+            Ok(event) => info!(?event, "Game event"),
+            Err(RecvError::Lagged(skipped)) => warn!("Event log skipped {skipped} events"),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ball::Ball,
+        game::Game,
+        physics::{Point, Velocity},
+    };
+
+    #[test]
+    fn events_in_order() {
+        let mut game = Game::new();
+        let mut events = game.subscribe();
+        game.connect_player(7, "alice");
+        game.connect_player(9, "bob");
+        game.skip_ahead();
+        // towards the left paddle, in the middle
+        game.balls_mut()[0] = Ball {
+            pos: Point::new(0.05, 0.5),
+            ..Ball::with_velocity(Velocity::new(-0.5, 0.0))
+        };
+        game.update();
+        // past the left paddle
+        game.skip_ahead();
+        game.balls_mut()[0] = Ball {
+            pos: Point::new(0.01, 0.95),
+            ..Ball::with_velocity(Velocity::new(-0.5, 0.0))
+        };
+        game.update();
+
+        let published: Vec<GameEvent> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(
+            published,
+            [
+                GameEvent::PlayerJoined {
+                    slot: 0,
+                    client_id: 7,
+                    name: "alice".to_string(),
+                },
+                GameEvent::PlayerJoined {
+                    slot: 1,
+                    client_id: 9,
+                    name: "bob".to_string(),
+                },
+                GameEvent::Serve,
+                GameEvent::PaddleHit { slot: 0 },
+                GameEvent::PointScored {
+                    team: 1,
+                    score: vec![0, 1],
+                },
+                GameEvent::Serve,
+            ]
+        );
+    }
+}
@@ -1,23 +1,58 @@
 use std::time::{Duration, Instant};
 
-use color_eyre::eyre::bail;
 use ratatui::{
+    layout::{Flex, Offset},
     prelude::*,
     widgets::{Block, Clear},
 };
 use tracing::info;
 
-use crate::{ball::Ball, paddle::Paddle, server::SshTerminal};
+use crate::{
+    ball::{Ball, Hit},
+    capabilities::{color_to_rgb, GlyphSupport},
+    court::{BigNumber, CourtMarkings, Marks, Net},
+    event::{EventBus, GameEvent},
+    graphics::{Rgb, Scene, Shape},
+    level::Level,
+    mode::{MatchConfig, Mode},
+    paddle::{Direction, Paddle},
+    physics::{Orientation, Point, Side, Velocity},
+    power_up::{PowerUp, PowerUpKind, PowerUps},
+    viewer::{Role, Viewer},
+};
+
+/// A player connected to the game.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub client_id: usize,
+    pub name: String,
+    /// The round trip time to the player's terminal, if it has been measured.
+    pub latency: Option<Duration>,
+    /// The most times in a row the player has returned the ball in practice.
+    pub practice_best: u32,
+}
 
 #[derive(Debug)]
 pub struct Game {
-    ball: Ball,
-    left_paddle: Paddle,
-    right_paddle: Paddle,
-    score: (u32, u32),
+    config: MatchConfig,
+    // The level being played, with its moving obstacles where they are now
+    level: Level,
+    // The balls in play, each scoring on its own. Extra balls are removed as they leave the
+    // court, and the last one is served again
+    balls: Vec<Ball>,
+    // The paddle for each player slot
+    paddles: Vec<Paddle>,
+    // Each team's points, or lives left in modes played with lives
+    score: Vec<u32>,
     serve_time: Option<Instant>,
     last_update: Option<Instant>,
-    clients: [Option<usize>; 2],
+    players: Vec<Option<Player>>,
+    // The number of times the ball has been returned since the last serve
+    rally: u32,
+    // The slot of the player who last hit the ball since the last serve
+    last_hitter: Option<usize>,
+    power_ups: PowerUps,
+    events: EventBus,
 }
 
 impl Default for Game {
@@ -29,120 +64,1168 @@ impl Default for Game {
 impl Game {
     // Wait for a fixed duration before serving the ball
     const SERVE_DURATION: Duration = Duration::from_millis(1500);
+    /// The score a player needs to win a match.
+    pub const WINNING_SCORE: u32 = 11;
+    /// The most players any mode has.
+    pub const MAX_PLAYERS: usize = 4;
+    // How much longer a paddle is with the long paddle power-up
+    const LONG_PADDLE_SCALE: f32 = 1.6;
+    // How much faster the ball moves with the fast ball power-up
+    const FAST_BALL_SPEED: f32 = 1.5;
+    // How much faster the ball gets with each return in practice, up to a limit
+    const PRACTICE_SPEEDUP: f32 = 0.1;
+    const MAX_PRACTICE_SPEED: f32 = 3.0;
 
     pub fn new() -> Self {
+        Self::with_config(MatchConfig::default())
+    }
+
+    pub fn with_config(config: MatchConfig) -> Self {
+        let mode = config.mode;
+        let paddles: Vec<_> = mode
+            .sides()
+            .iter()
+            .enumerate()
+            .map(|(slot, &side)| {
+                let (start, end) = mode.lane(slot);
+                Paddle::new(side)
+                    .with_lane(start, end)
+                    .with_handicap(config.handicaps[slot])
+            })
+            .collect();
         Self {
-            ball: Ball::new(),
-            left_paddle: Paddle::new(0.0, 0.5),
-            right_paddle: Paddle::new(1.0, 0.5),
-            score: (0, 0),
+            level: config.level.clone(),
+            balls: vec![Ball::new()],
+            players: vec![None; paddles.len()],
+            paddles,
+            score: Self::initial_score(config.mode),
             serve_time: None,
             last_update: None,
-            clients: [None, None],
+            rally: 0,
+            last_hitter: None,
+            power_ups: PowerUps::new(),
+            events: EventBus::new(),
+            config,
         }
     }
 
-    pub fn connect_player(&mut self, client_id: usize) -> color_eyre::Result<()> {
-        if self.clients[0].is_none() {
-            info!("Player 1 connected");
-            self.clients[0] = Some(client_id);
-        } else if self.clients[1].is_none() {
-            info!("Player 2 connected");
-            self.clients[1] = Some(client_id);
-        } else {
-            bail!("Game is full");
-        }
-        if self.clients.iter().all(Option::is_some) {
-            info!("Both players connected, starting game");
-            self.score = (0, 0);
+    fn initial_score(mode: Mode) -> Vec<u32> {
+        vec![mode.lives().unwrap_or(0); mode.teams()]
+    }
+
+    pub fn config(&self) -> &MatchConfig {
+        &self.config
+    }
+
+    /// Whether nobody is playing, so the next player to join can choose the match options.
+    pub fn is_empty(&self) -> bool {
+        self.players.iter().all(Option::is_none)
+    }
+
+    /// Start over with new match options. Subscribers keep receiving events.
+    ///
+    /// Should only be called while the game [is empty](Self::is_empty), as the players' slots are
+    /// reset.
+    pub fn configure(&mut self, config: MatchConfig) {
+        let events = self.events.clone();
+        *self = Self {
+            events,
+            ..Self::with_config(config)
+        };
+    }
+
+    /// Subscribe to everything that happens in the game from now on.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<GameEvent> {
+        self.events.subscribe()
+    }
+
+    /// Add a client to the game.
+    ///
+    /// Clients take the free player slots in order until the match has enough players, and anyone
+    /// else who connects watches as a spectator.
+    pub fn connect_player(&mut self, client_id: usize, name: &str) -> Role {
+        let player = Some(Player {
+            client_id,
+            name: name.to_string(),
+            latency: None,
+            practice_best: 0,
+        });
+        let Some(slot) = self.players.iter().position(Option::is_none) else {
+            info!("Game is full, spectator connected");
+            return Role::Spectator;
+        };
+        self.players[slot] = player;
+        self.events.publish(GameEvent::PlayerJoined {
+            slot,
+            client_id,
+            name: name.to_string(),
+        });
+        if self.players.iter().all(Option::is_some) {
+            info!("All players connected, starting game");
+            self.score = Self::initial_score(self.config.mode);
             self.serve();
         }
-        Ok(())
+        self.role(client_id)
+    }
+
+    /// Set the personal best in practice of the given client, from their profile.
+    pub fn set_practice_best(&mut self, client_id: usize, best: u32) {
+        if let Some(slot) = self.slot(client_id) {
+            if let Some(player) = &mut self.players[slot] {
+                player.practice_best = best;
+            }
+        }
+    }
+
+    /// Set the measured round trip time to the given client, shown next to their name.
+    pub fn set_latency(&mut self, client_id: usize, latency: Duration) {
+        if let Some(slot) = self.slot(client_id) {
+            if let Some(player) = &mut self.players[slot] {
+                player.latency = Some(latency);
+            }
+        }
     }
 
     pub fn disconnect_player(&mut self, client_id: usize) {
-        if let Some(id) = self.clients.iter_mut().find(|id| **id == Some(client_id)) {
-            info!("Player disconnected");
-            *id = None;
+        if let Some(slot) = self.slot(client_id) {
+            if let Some(player) = self.players[slot].take() {
+                self.events.publish(GameEvent::PlayerLeft {
+                    slot,
+                    client_id,
+                    name: player.name,
+                });
+            }
         }
     }
 
-    pub fn draw(&mut self, terminal: &mut SshTerminal) -> color_eyre::Result<()> {
-        terminal.draw(|frame| frame.render_widget(self, frame.area()))?;
-        Ok(())
+    /// The index of the player slot used by the given client, if they are playing.
+    fn slot(&self, client_id: usize) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|player| player.as_ref().is_some_and(|p| p.client_id == client_id))
     }
 
-    pub fn move_up(&mut self, client_id: usize) {
-        if self.clients[0]
-            .as_ref()
-            .map_or(false, |id| *id == client_id)
-        {
-            self.left_paddle.move_up();
-        } else if self.clients[1]
-            .as_ref()
-            .map_or(false, |id| *id == client_id)
-        {
-            self.right_paddle.move_up();
+    /// The paddle controlled by the given client, if they are playing.
+    fn paddle_mut(&mut self, client_id: usize) -> Option<&mut Paddle> {
+        self.slot(client_id).map(|slot| &mut self.paddles[slot])
+    }
+
+    /// Whether the player in the given slot is still in the match, rather than their team having
+    /// run out of lives.
+    pub fn is_playing(&self, slot: usize) -> bool {
+        self.is_team_playing(self.config.mode.team(slot))
+    }
+
+    fn is_team_playing(&self, team: usize) -> bool {
+        self.config.mode.lives().is_none() || self.score[team] > 0
+    }
+
+    pub fn press(&mut self, client_id: usize, direction: Direction, release_expected: bool) {
+        if let Some(paddle) = self.paddle_mut(client_id) {
+            paddle.press(direction, release_expected);
         }
     }
 
-    pub fn move_down(&mut self, client_id: usize) {
-        if self.clients[0]
-            .as_ref()
-            .map_or(false, |id| *id == client_id)
-        {
-            self.left_paddle.move_down();
-        } else if self.clients[1]
-            .as_ref()
-            .map_or(false, |id| *id == client_id)
-        {
-            self.right_paddle.move_down();
+    pub fn release(&mut self, client_id: usize, direction: Direction) {
+        if let Some(paddle) = self.paddle_mut(client_id) {
+            paddle.release(direction);
         }
     }
 
+    /// Move the client's paddle towards the given position in their terminal.
+    ///
+    /// Paddles on the left and right follow the row, and paddles on the top and bottom follow the
+    /// column.
+    pub fn move_to_position(
+        &mut self,
+        client_id: usize,
+        position: Position,
+        area: Rect,
+        cell_aspect: f32,
+    ) {
+        let playfield = Self::playfield_area(area, cell_aspect);
+        let target = Point::from_screen(position, playfield);
+        if let Some(paddle) = self.paddle_mut(client_id) {
+            paddle.move_to(paddle.orientation().along(target));
+        }
+    }
+
+    /// The width of the court relative to its height, as it appears on screen.
+    ///
+    /// Every client sees a court of the same shape, so the ball travels at the same angles and
+    /// speeds regardless of terminal size.
+    const ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+    /// The smallest court that is still playable, in cells.
+    pub const MIN_SIZE: Size = Size::new(40, 15);
+
+    /// The width of the court relative to its height in cells, for cells of the given shape.
+    fn aspect_in_cells(cell_aspect: f32) -> f32 {
+        Self::ASPECT_RATIO * cell_aspect
+    }
+
+    /// The largest area with the court's aspect ratio, centered in the terminal.
+    ///
+    /// The rest of the terminal is left empty, like letterboxing a film. `cell_aspect` is the
+    /// height of a cell relative to its width (see [`SshBackend::cell_aspect`]).
+    ///
+    /// [`SshBackend::cell_aspect`]: crate::backend::SshBackend::cell_aspect
+    pub fn court_area(area: Rect, cell_aspect: f32) -> Rect {
+        let aspect = Self::aspect_in_cells(cell_aspect);
+        let height = f32::from(area.height).min(f32::from(area.width) / aspect);
+        let width = (height * aspect).round() as u16;
+        let height = height.round() as u16;
+        let [area] = Layout::horizontal([Constraint::Length(width.min(area.width))])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        area
+    }
+
+    /// Whether the terminal is too small to show a playable court.
+    pub fn is_too_small(area: Rect, cell_aspect: f32) -> bool {
+        let court = Self::court_area(area, cell_aspect);
+        court.width < Self::MIN_SIZE.width || court.height < Self::MIN_SIZE.height
+    }
+
+    /// The area inside the border where the ball and paddles are drawn.
+    pub fn playfield_area(area: Rect, cell_aspect: f32) -> Rect {
+        Self::border().inner(Self::court_area(area, cell_aspect))
+    }
+
+    fn border() -> Block<'static> {
+        Block::bordered()
+            .title("Pong")
+            .title_alignment(Alignment::Center)
+    }
+
+    /// How the given client takes part in the game.
+    pub fn role(&self, client_id: usize) -> Role {
+        self.slot(client_id).map_or(Role::Spectator, Role::Player)
+    }
+
+    /// A widget that draws the game from the given viewer's perspective.
+    pub fn view<'a>(&'a self, viewer: &'a Viewer<'a>) -> GameView<'a> {
+        GameView { game: self, viewer }
+    }
+
+    /// Advance the game to the current time, publishing what happened to subscribers.
     pub fn update(&mut self) {
+        let duration = self.last_update.map_or(Duration::ZERO, |t| t.elapsed());
+        self.last_update = Some(Instant::now());
+        for paddle in &mut self.paddles {
+            paddle.update(duration);
+        }
+        self.level.update(duration);
+
         if self
             .serve_time
-            .map_or(true, |t| t.elapsed() < Self::SERVE_DURATION)
+            .is_none_or(|t| t.elapsed() < Self::SERVE_DURATION)
         {
             return;
         }
-        let duration = self.last_update.map_or(Duration::ZERO, |t| t.elapsed());
-        self.last_update = Some(Instant::now());
-        self.ball
-            .update(duration, &self.left_paddle, &self.right_paddle);
+        // the sides of players who are out are walls
+        let slots: Vec<usize> = (0..self.paddles.len())
+            .filter(|&slot| self.is_playing(slot))
+            .collect();
+        let paddles: Vec<&Paddle> = slots.iter().map(|&slot| &self.paddles[slot]).collect();
+        let mut exited = Vec::new();
+        let rally = self.rally;
+        for (index, ball) in self.balls.iter_mut().enumerate() {
+            match ball.update(duration, &paddles, &self.level, self.config.physics) {
+                Some(Hit::Wall) => self.events.publish(GameEvent::WallBounce),
+                Some(Hit::Paddle(paddle)) => {
+                    self.rally += 1;
+                    self.last_hitter = Some(slots[paddle]);
+                    self.events.publish(GameEvent::PaddleHit {
+                        slot: slots[paddle],
+                    });
+                }
+                None => {}
+            }
+            if let Some(side) = ball.exit_side() {
+                exited.push((index, side));
+            }
+        }
+        if self.rally != rally {
+            self.apply_ball_speed();
+        }
+        if self.config.power_ups {
+            self.update_power_ups();
+        }
 
-        if self.ball.pos.x < 0.0 {
-            self.score.1 += 1;
-            self.serve();
-        } else if self.ball.pos.x > 1.0 {
-            self.score.0 += 1;
-            self.serve();
+        // in reverse, so removing a ball doesn't move the ones still to be handled
+        let mut match_ended = false;
+        for (index, side) in exited.into_iter().rev() {
+            let Some(slot) = self.paddles.iter().position(|paddle| paddle.side == side) else {
+                // a side without a paddle is always a wall, so the ball can't leave through it
+                continue;
+            };
+            let team = self.config.mode.team(slot);
+            // balls that leave once the team is out or the match is over don't count
+            if !match_ended && self.is_team_playing(team) {
+                if self.config.mode == Mode::Practice {
+                    self.end_run(slot);
+                } else if self.config.mode.lives().is_some() {
+                    match_ended = self.lose_life(team);
+                } else {
+                    // modes played for points have two teams
+                    match_ended = self.score_point(1 - team);
+                }
+            }
+            if self.balls.len() > 1 {
+                self.balls.remove(index);
+            } else {
+                self.serve();
+            }
+        }
+    }
+
+    /// The given team won a point, returning whether that won them the match.
+    fn score_point(&mut self, team: usize) -> bool {
+        self.score[team] += 1;
+        self.events.publish(GameEvent::PointScored {
+            team,
+            score: self.score.clone(),
+        });
+        if self.score[team] < Self::WINNING_SCORE {
+            return false;
+        }
+        self.end_match(team);
+        true
+    }
+
+    /// The given team let the ball past, returning whether that ended the match.
+    fn lose_life(&mut self, team: usize) -> bool {
+        self.score[team] = self.score[team].saturating_sub(1);
+        self.events.publish(GameEvent::LifeLost {
+            team,
+            lives: self.score[team],
+        });
+        if self.score[team] == 0 {
+            self.events.publish(GameEvent::PlayerEliminated { team });
+        }
+        let mut remaining = (0..self.score.len()).filter(|&team| self.is_team_playing(team));
+        let (Some(winner), None) = (remaining.next(), remaining.next()) else {
+            return false;
+        };
+        self.end_match(winner);
+        true
+    }
+
+    /// The player in the given slot let the ball past in practice.
+    fn end_run(&mut self, slot: usize) {
+        let returns = self.rally;
+        if let Some(player) = &mut self.players[slot] {
+            player.practice_best = player.practice_best.max(returns);
+        }
+        self.events.publish(GameEvent::RunEnded { slot, returns });
+    }
+
+    fn end_match(&mut self, winner: usize) {
+        self.events.publish(GameEvent::MatchEnded {
+            winner,
+            score: self.score.clone(),
+        });
+        self.score = Self::initial_score(self.config.mode);
+        self.power_ups.clear();
+        self.apply_power_ups();
+    }
+
+    /// Spawn, collect and expire power-ups.
+    ///
+    /// Any ball collects power-ups on behalf of the player who last hit a ball, so nothing is
+    /// collected straight after a serve.
+    fn update_power_ups(&mut self) {
+        for expired in self.power_ups.update() {
+            self.events.publish(GameEvent::PowerUpExpired {
+                kind: expired.kind,
+                slot: expired.slot,
+            });
+        }
+        if let Some(slot) = self.last_hitter {
+            let collected = self.balls.iter().find_map(|ball| {
+                let kind = self.power_ups.collect(ball.pos)?;
+                Some((kind, ball.clone()))
+            });
+            if let Some((kind, ball)) = collected {
+                let mode = self.config.mode;
+                let slots: Vec<_> = match kind {
+                    PowerUpKind::LongPaddle => vec![Some(slot)],
+                    PowerUpKind::FastBall => vec![None],
+                    PowerUpKind::ReversedControls => (0..self.paddles.len())
+                        .filter(|&other| mode.team(other) != mode.team(slot))
+                        .map(Some)
+                        .collect(),
+                    // the extra balls aren't timed, so nothing is put into effect
+                    PowerUpKind::MultiBall => {
+                        self.split_ball(&ball);
+                        Vec::new()
+                    }
+                };
+                self.power_ups.activate(kind, &slots);
+                self.events
+                    .publish(GameEvent::PowerUpCollected { slot, kind });
+            }
         }
+        self.apply_power_ups();
     }
 
+    /// Set the paddles and ball to match the power-ups in effect.
+    fn apply_power_ups(&mut self) {
+        for (slot, paddle) in self.paddles.iter_mut().enumerate() {
+            let long = self
+                .power_ups
+                .is_active(PowerUpKind::LongPaddle, Some(slot));
+            let scale = if long { Self::LONG_PADDLE_SCALE } else { 1.0 };
+            paddle.length = Paddle::LENGTH * self.config.handicaps[slot].length * scale;
+            paddle.set_reversed(
+                self.power_ups
+                    .is_active(PowerUpKind::ReversedControls, Some(slot)),
+            );
+        }
+        self.apply_ball_speed();
+    }
+
+    /// Set the speed of the balls for the fast ball power-up and, in practice, the returns so
+    /// far.
+    fn apply_ball_speed(&mut self) {
+        let fast = self.power_ups.is_active(PowerUpKind::FastBall, None);
+        let mut speed = if fast { Self::FAST_BALL_SPEED } else { 1.0 };
+        if self.config.mode == Mode::Practice {
+            speed *=
+                (1.0 + self.rally as f32 * Self::PRACTICE_SPEEDUP).min(Self::MAX_PRACTICE_SPEED);
+        }
+        for ball in &mut self.balls {
+            ball.speed = speed;
+        }
+    }
+
+    /// Add two balls where the given ball is, heading the same way at steeper angles.
+    fn split_ball(&mut self, ball: &Ball) {
+        for y in [Velocity::VALID_Y[1], Velocity::VALID_Y[5]] {
+            self.balls.push(Ball {
+                vel: Velocity::new(ball.vel.x, y),
+                ..ball.clone()
+            });
+        }
+    }
+
+    /// The power-ups in effect for the player in the given slot, or the whole game for `None`,
+    /// with how long they have left.
+    pub fn active_power_ups(&self, slot: Option<usize>) -> Vec<(PowerUpKind, Duration)> {
+        self.power_ups
+            .active
+            .iter()
+            .filter(|active| active.slot == slot)
+            .map(|active| (active.kind, active.remaining()))
+            .collect()
+    }
+
+    /// The player in the given slot, if there is one.
+    pub fn player(&self, slot: usize) -> Option<&Player> {
+        self.players[slot].as_ref()
+    }
+
+    /// The name of the player in the given slot, or a placeholder if the slot is empty.
+    pub fn player_name(&self, slot: usize) -> String {
+        self.player(slot).map_or_else(
+            || format!("Player {}", slot + 1),
+            |player| player.name.clone(),
+        )
+    }
+
+    /// The names of the players in the given team, such as "alice and bob".
+    pub fn team_name(&self, team: usize) -> String {
+        let mode = self.config.mode;
+        let names: Vec<_> = (0..self.players.len())
+            .filter(|&slot| mode.team(slot) == team)
+            .map(|slot| self.player_name(slot))
+            .collect();
+        names.join(" and ")
+    }
+
+    pub fn balls(&self) -> &[Ball] {
+        &self.balls
+    }
+
+    /// Put the match's number of balls back in the center, ready to be served.
+    ///
+    /// The first ball keeps its velocity, so serves alternate between the sides as before. Each
+    /// pair of extra balls heads off in opposite directions at a different angle.
     pub fn serve(&mut self) {
-        info!("Serving ball");
-        self.ball.serve();
+        self.events.publish(GameEvent::Serve);
+        let vel = self.balls.first().map_or(Ball::new().vel, |ball| ball.vel);
+        self.balls.truncate(1);
+        if let Some(ball) = self.balls.first_mut() {
+            ball.serve();
+        }
+        // the other pairs of balls head off at different angles to the first
+        let angles: Vec<f32> = Velocity::VALID_Y
+            .into_iter()
+            .filter(|&y| y != vel.y)
+            .collect();
+        for index in 1..self.config.balls {
+            let x = if index % 2 == 0 { vel.x } else { -vel.x };
+            let y = match index / 2 {
+                0 => vel.y,
+                pair => angles[((pair - 1) * 2) % angles.len()],
+            };
+            self.balls.push(Ball::with_velocity(Velocity::new(x, y)));
+        }
+        self.rally = 0;
+        self.apply_power_ups();
+        self.last_hitter = None;
         self.serve_time = Some(Instant::now());
-        self.last_update = None;
     }
 }
 
-impl Widget for &mut Game {
+/// The game as seen by one viewer. See [`Game::view`].
+///
+/// The viewer's own paddle and name are drawn in their chosen color and labelled "you". When the
+/// viewer prefers a mirrored court and plays on the right, the court is flipped so their paddle
+/// appears on the left.
+pub struct GameView<'a> {
+    game: &'a Game,
+    viewer: &'a Viewer<'a>,
+}
+
+impl GameView<'_> {
+    // How many shades the ball's trail fades through when drawn with graphics
+    const TRAIL_FADE_STEPS: f32 = 4.0;
+
+    /// Whether the court should be drawn flipped horizontally for this viewer, so their paddle is
+    /// on the left.
+    fn mirrored(&self) -> bool {
+        match self.viewer.role {
+            Role::Player(slot) => {
+                self.viewer.preferences.mirror && self.game.paddles[slot].side == Side::Right
+            }
+            Role::Spectator => false,
+        }
+    }
+
+    /// The balls and paddles, as seen by the viewer.
+    ///
+    /// The paddles of players who are out of the match are left out.
+    fn objects(&self) -> (Vec<Ball>, Vec<(usize, Paddle)>) {
+        let game = self.game;
+        let mut balls = game.balls.clone();
+        let mut paddles: Vec<_> = (0..game.paddles.len())
+            .filter(|&slot| game.is_playing(slot))
+            .map(|slot| (slot, game.paddles[slot].clone()))
+            .collect();
+        if self.mirrored() {
+            for ball in &mut balls {
+                ball.pos = ball.pos.mirrored();
+            }
+            for (_, paddle) in &mut paddles {
+                paddle.pos = paddle.pos.mirrored();
+            }
+        }
+        (balls, paddles)
+    }
+
+    /// The ball and paddles to draw with graphics, for a terminal with cells of the given size in
+    /// pixels.
+    ///
+    /// The net and scores are included as the backdrop. Returns `None` if the terminal is too
+    /// small to show the court.
+    pub fn scene(&self, area: Rect, cell_size: Size) -> Option<Scene> {
+        let cell_aspect = self.viewer.cell_aspect;
+        if Game::is_too_small(area, cell_aspect) {
+            return None;
+        }
+        let area = Game::border().inner(self.court_area(area));
+        let backdrop = self
+            .court_marks(area)
+            .iter()
+            .flat_map(|(marks, color)| marks.shapes(area, cell_size, color_to_rgb(*color)))
+            .collect();
+        let mut scene = Scene {
+            area,
+            cell_size,
+            background: color_to_rgb(self.viewer.theme.background),
+            shapes: Vec::new(),
+            backdrop,
+            covered: Vec::new(),
+        };
+        let (width, height) = scene.size();
+        let size = u32::from(cell_size.width);
+        let (balls, paddles) = self.objects();
+        for (slot, paddle) in &paddles {
+            let color = color_to_rgb(self.paddle_color(*slot));
+            let start = (paddle.offset() - paddle.length / 2.0).max(0.0);
+            scene.shapes.push(match paddle.orientation() {
+                Orientation::Vertical => Shape {
+                    x: (paddle.pos.x * width.saturating_sub(size) as f32) as u32,
+                    y: (start * height as f32) as u32,
+                    width: size,
+                    height: (paddle.length * height as f32) as u32,
+                    color,
+                },
+                Orientation::Horizontal => {
+                    // as thick as the paddles on the sides are wide
+                    Shape {
+                        x: (start * width as f32) as u32,
+                        y: (paddle.pos.y * height.saturating_sub(size) as f32) as u32,
+                        width: (paddle.length * width as f32) as u32,
+                        height: size,
+                        color,
+                    }
+                }
+            });
+        }
+        // the ball is square in pixels, whatever the shape of the cells
+        let ball_color = color_to_rgb(self.viewer.theme.ball);
+        let ball_shape = |pos: Point, size: u32, color| {
+            // centered on where the full size ball would be
+            let full_size = u32::from(cell_size.width);
+            let offset = (full_size - size) / 2;
+            Shape {
+                x: (pos.x.clamp(0.0, 1.0) * width.saturating_sub(full_size) as f32) as u32 + offset,
+                y: (pos.y.clamp(0.0, 1.0) * height.saturating_sub(full_size) as f32) as u32
+                    + offset,
+                width: size,
+                height: size,
+                color,
+            }
+        };
+        for (pos, age) in self.trail() {
+            // faded in a few steps, so kitty terminals can reuse the image for each color
+            let age = (age * Self::TRAIL_FADE_STEPS).ceil() / Self::TRAIL_FADE_STEPS;
+            let trail_size = ((size as f32 * (1.0 - age * 0.6)) as u32).max(1);
+            let color = blend(ball_color, scene.background, age);
+            scene.shapes.push(ball_shape(pos, trail_size, color));
+        }
+        if let Some(power_up) = self.power_up() {
+            let color = color_to_rgb(self.viewer.theme.score);
+            scene.shapes.push(ball_shape(power_up.pos, size, color));
+        }
+        for ball in &balls {
+            scene.shapes.push(ball_shape(ball.pos, size, ball_color));
+        }
+        Some(scene)
+    }
+
+    /// The power-up waiting to be collected, as seen by the viewer.
+    fn power_up(&self) -> Option<PowerUp> {
+        let mut power_up = self.game.power_ups.spawned?;
+        if self.mirrored() {
+            power_up.pos = power_up.pos.mirrored();
+        }
+        Some(power_up)
+    }
+
+    /// The court within the terminal, moved sideways while it is shaking after a point.
+    fn court_area(&self, area: Rect) -> Rect {
+        let court = Game::court_area(area, self.viewer.cell_aspect);
+        if !self.viewer.preferences.score_shake {
+            return court;
+        }
+        court
+            .offset(Offset {
+                x: self.viewer.effects.shake_offset(),
+                y: 0,
+            })
+            .intersection(area)
+    }
+
+    /// The color of the paddle in the given slot.
+    ///
+    /// Paddles flash in the ball's color when they return it, or in another color in themes
+    /// where the ball is the same color as the paddle.
+    fn paddle_color(&self, slot: usize) -> Color {
+        let viewer = self.viewer;
+        let color = if viewer.role == Role::Player(slot) {
+            viewer.preferences.own_color
+        } else {
+            viewer.theme.paddle
+        };
+        if !(viewer.preferences.hit_flash && viewer.effects.paddle_flashing(slot)) {
+            return color;
+        }
+        [viewer.theme.ball, viewer.theme.score, Color::LightYellow]
+            .into_iter()
+            .find(|&flash| flash != color)
+            .unwrap_or(Color::LightCyan)
+    }
+
+    /// The positions of the balls' trails as seen by the viewer, with how faded each one is.
+    fn trail(&self) -> Vec<(Point, f32)> {
+        if !self.viewer.preferences.trail {
+            return Vec::new();
+        }
+        let mirrored = self.mirrored();
+        self.viewer
+            .effects
+            .trail()
+            .map(|(pos, age)| (if mirrored { pos.mirrored() } else { pos }, age))
+            .collect()
+    }
+
+    /// The net, scores and markings drawn on the playfield, with their colors.
+    ///
+    /// Modes played with lives have no net, and show the lives in the player labels instead of
+    /// the scores. Practice has no net either, and shows the returns so far.
+    fn court_marks(&self, area: Rect) -> Vec<(Marks, Color)> {
+        let theme = self.viewer.theme;
+        let score = &self.game.score;
+        let (left, right) = if self.mirrored() { (1, 0) } else { (0, 1) };
+        let mut marks = Vec::new();
+        let mode = self.game.config.mode;
+        // modes played with lives show them in the labels instead
+        let points = mode.lives().is_none() && mode != Mode::Practice;
+        if points {
+            marks.push((Net.marks(area), theme.foreground));
+        }
+        if self.viewer.preferences.court_markings {
+            marks.push((CourtMarkings.marks(area), theme.foreground));
+        }
+        let height = BigNumber::scaled_height(area.height);
+        if mode == Mode::Practice {
+            let digits_area = Rect {
+                y: area.y + 1,
+                height,
+                ..area
+            }
+            .intersection(area);
+            marks.push((
+                BigNumber::new(self.game.rally, height).marks(digits_area),
+                theme.score,
+            ));
+        }
+        if points {
+            let [left_half, right_half] = Layout::horizontal([Constraint::Fill(1); 2]).areas(area);
+            for (team, half) in [(left, left_half), (right, right_half)] {
+                let digits_area = Rect {
+                    y: half.y + 1,
+                    height,
+                    ..half
+                }
+                .intersection(area);
+                marks.push((
+                    BigNumber::new(score[team], height).marks(digits_area),
+                    theme.score,
+                ));
+            }
+        }
+        let (level, guarded) = self.level();
+        let (solid, portals) = level.marks(area, &guarded);
+        marks.push((solid, theme.foreground));
+        marks.push((portals, theme.ball));
+        marks
+    }
+
+    /// The level as seen by the viewer, and the sides of the court guarded by paddles.
+    fn level(&self) -> (Level, Vec<Side>) {
+        let game = self.game;
+        let mirrored = self.mirrored();
+        let guarded = (0..game.paddles.len())
+            .filter(|&slot| game.is_playing(slot))
+            .map(|slot| match (game.paddles[slot].side, mirrored) {
+                (Side::Left, true) => Side::Right,
+                (Side::Right, true) => Side::Left,
+                (side, _) => side,
+            })
+            .collect();
+        if mirrored {
+            (game.level.mirrored(), guarded)
+        } else {
+            (game.level.clone(), guarded)
+        }
+    }
+
+    fn own_style(&self) -> Style {
+        let capabilities = self.viewer.capabilities;
+        Style::new().fg(capabilities.color(self.viewer.preferences.own_color))
+    }
+
+    /// The heads up display label for the player in the given slot.
+    fn player_label(&self, slot: usize) -> Line<'static> {
+        let capabilities = self.viewer.capabilities;
+        let Some(player) = &self.game.players[slot] else {
+            let waiting = capabilities.text(" waiting for player… ", " waiting for player... ");
+            return Line::from(waiting).italic();
+        };
+        let separator = capabilities.text(" · ", " - ");
+        let mut label = format!(" {}", player.name);
+        if self.viewer.role == Role::Player(slot) {
+            label += " (you)";
+        }
+        match self.game.score[self.game.config.mode.team(slot)] {
+            _ if self.game.config.mode.lives().is_none() => {}
+            0 => label += &format!("{separator}out"),
+            1 => label += &format!("{separator}1 life"),
+            lives => label += &format!("{separator}{lives} lives"),
+        }
+        if self.game.config.mode == Mode::Practice {
+            label += &format!("{separator}best {}", player.practice_best);
+        }
+        for (kind, remaining) in self.game.active_power_ups(Some(slot)) {
+            label += &format!("{separator}{} {}", kind.name(), format_timer(remaining));
+        }
+        if let Some(latency) = player.latency {
+            label += &format!(" {}ms", latency.as_millis());
+        }
+        label.push(' ');
+        if self.viewer.role == Role::Player(slot) {
+            Line::from(label).patch_style(self.own_style()).bold()
+        } else {
+            Line::from(label)
+        }
+    }
+}
+
+impl Widget for GameView<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        let game = self.game;
+        let mirrored = self.mirrored();
+
         Clear.render(area, buf);
-        let block = Block::bordered()
-            .title("Pong")
-            .title_alignment(Alignment::Center)
-            .style((Color::White, Color::DarkGray));
-        (&block).render(area, buf);
-        let area = block.inner(area);
-        Line::from(format!("Score: {} - {}", self.score.0, self.score.1))
-            .centered()
-            .render(area, buf);
-        self.ball.render(area, buf);
-        self.left_paddle.render(area, buf);
-        self.right_paddle.render(area, buf);
+        let cell_aspect = self.viewer.cell_aspect;
+        if Game::is_too_small(area, cell_aspect) {
+            return render_too_small(area, cell_aspect, buf);
+        }
+        let court = self.court_area(area);
+        let capabilities = self.viewer.capabilities;
+        let theme = self.viewer.theme;
+        let color = |color| capabilities.color(color);
+        let mut border = Game::border()
+            .border_set(capabilities.border_set())
+            .style((color(theme.foreground), color(theme.background)))
+            .title_bottom(Line::from(format!(" Rally {} ", game.rally)).centered());
+        // each player's label goes on the edge nearest their paddle
+        for (slot, paddle) in game.paddles.iter().enumerate() {
+            let label = self.player_label(slot);
+            border = match (paddle.side, mirrored) {
+                (Side::Left, false) | (Side::Right, true) => border.title_top(label.left_aligned()),
+                (Side::Right, false) | (Side::Left, true) => {
+                    border.title_top(label.right_aligned())
+                }
+                (Side::Top, _) => border.title_top(label.centered()),
+                (Side::Bottom, _) => border.title_bottom(label.centered()),
+            };
+        }
+        if self.viewer.role == Role::Spectator {
+            border = border.title_bottom(Line::from(" spectating ").italic().left_aligned());
+        }
+        for (kind, remaining) in game.active_power_ups(None) {
+            let timer = format!(" {} {} ", kind.name(), format_timer(remaining));
+            border = border.title_bottom(Line::from(timer).right_aligned());
+        }
+        if self.viewer.preferences.score_shake && self.viewer.effects.shaking() {
+            border = border.border_style(Style::new().fg(color(theme.score)).reversed());
+        }
+        border.render(court, buf);
+        let area = Game::border().inner(court);
+        for (marks, mark_color) in self.court_marks(area) {
+            marks.render(buf, capabilities.glyphs, Style::new().fg(color(mark_color)));
+        }
+
+        if self.viewer.graphics {
+            // the ball and paddles are drawn as images after the frame
+            return;
+        }
+        if let Some(power_up) = self.power_up() {
+            let screen = power_up.pos.to_screen(area);
+            let symbol = power_up.kind.symbol(capabilities.glyphs);
+            buf.set_string(screen.x, screen.y, symbol, color(theme.score));
+        }
+        let (balls, paddles) = self.objects();
+        let trail_style = Style::new().fg(color(theme.ball));
+        for (pos, age) in self.trail() {
+            let (symbol, style) = match (capabilities.glyphs, age < 0.5) {
+                (GlyphSupport::Ascii, true) => ("o", trail_style),
+                (GlyphSupport::Ascii, false) => (".", trail_style.dim()),
+                (_, true) => ("•", trail_style),
+                (_, false) => ("·", trail_style.dim()),
+            };
+            let screen = pos.to_screen(area);
+            buf.set_string(screen.x, screen.y, symbol, style);
+        }
+        let ball_glyphs = capabilities.ball_glyphs(self.viewer.preferences.ball_glyphs);
+        for ball in &balls {
+            ball.widget(ball_glyphs)
+                .style(color(theme.ball))
+                .cell_aspect(cell_aspect)
+                .render(area, buf);
+        }
+        for (slot, paddle) in &paddles {
+            paddle
+                .widget(capabilities.glyphs)
+                .style(color(self.paddle_color(*slot)))
+                .render(area, buf);
+        }
+    }
+}
+
+/// Explain that the terminal needs to be resized, in place of the court.
+fn render_too_small(area: Rect, cell_aspect: f32, buf: &mut Buffer) {
+    let aspect = Game::aspect_in_cells(cell_aspect);
+    let min = Game::MIN_SIZE;
+    // the court is limited by whichever dimension is shortest relative to the aspect ratio
+    let needed = Size::new(
+        min.width
+            .max((f32::from(min.height) * aspect).ceil() as u16),
+        min.height
+            .max((f32::from(min.width) / aspect).ceil() as u16),
+    );
+    let text = Text::from(vec![
+        Line::from("Terminal too small").bold(),
+        Line::from(format!(
+            "Resize to at least {}x{} (currently {}x{})",
+            needed.width, needed.height, area.width, area.height
+        )),
+    ])
+    .centered();
+    let [area] = Layout::vertical([Constraint::Length(text.height() as u16)])
+        .flex(Flex::Center)
+        .areas(area);
+    text.render(area, buf);
+}
+
+/// A power-up's time left in whole seconds, rounded up so it never shows 0s.
+fn format_timer(remaining: Duration) -> String {
+    format!("{}s", remaining.as_secs_f32().ceil())
+}
+
+/// Mix two colors, from all `from` at 0 to all `to` at 1.
+fn blend(from: Rgb, to: Rgb, amount: f32) -> Rgb {
+    let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * amount) as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+#[cfg(test)]
+impl Game {
+    /// Get ready to update the game a tenth of a second after the serve.
+    pub(crate) fn skip_ahead(&mut self) {
+        let now = Instant::now();
+        self.serve_time = now.checked_sub(Self::SERVE_DURATION);
+        self.last_update = now.checked_sub(Duration::from_millis(100));
+    }
+
+    pub(crate) fn balls_mut(&mut self) -> &mut [Ball] {
+        &mut self.balls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paddle::Handicap;
+
+    /// A game with every slot filled, about to be updated a tenth of a second after the serve.
+    fn started(config: MatchConfig) -> Game {
+        let mut game = Game::with_config(config);
+        for client_id in 0..game.players.len() {
+            game.connect_player(client_id, "player");
+        }
+        game.skip_ahead();
+        game
+    }
+
+    #[test]
+    fn doubles_team_scores() {
+        let mut game = started(MatchConfig {
+            mode: Mode::Doubles,
+            ..MatchConfig::default()
+        });
+        // past the bottom left paddle, which belongs to the left team
+        game.balls[0] = Ball {
+            pos: Point::new(0.01, 0.9),
+            ..Ball::with_velocity(Velocity::new(-0.5, 0.0))
+        };
+        game.update();
+        assert_eq!(game.score, [0, 1]);
+    }
+
+    #[test]
+    fn long_and_reversed_paddles() {
+        let mut handicaps = [Handicap::default(); Game::MAX_PLAYERS];
+        handicaps[0].length = 0.5;
+        let mut game = started(MatchConfig {
+            handicaps,
+            ..MatchConfig::default()
+        });
+        game.power_ups
+            .activate(PowerUpKind::LongPaddle, &[Some(0), Some(1)]);
+        game.power_ups
+            .activate(PowerUpKind::ReversedControls, &[Some(1)]);
+        game.apply_power_ups();
+        // the power-up scales the handicapped length
+        let scaled = Paddle::LENGTH * Game::LONG_PADDLE_SCALE;
+        assert!((game.paddles[0].length - scaled * 0.5).abs() < 1e-6);
+        assert!((game.paddles[1].length - scaled).abs() < 1e-6);
+
+        // the reversed paddle heads the other way to the mouse
+        for paddle in &mut game.paddles {
+            paddle.move_to(0.2);
+            paddle.update(Duration::from_secs(1));
+        }
+        assert!(game.paddles[0].offset() < 0.5);
+        assert!(game.paddles[1].offset() > 0.5);
+
+        // and both go back to normal once the power-ups run out
+        game.power_ups.active.clear();
+        game.apply_power_ups();
+        assert!((game.paddles[0].length - Paddle::LENGTH * 0.5).abs() < 1e-6);
+        assert!((game.paddles[1].length - Paddle::LENGTH).abs() < 1e-6);
+        game.paddles[1].move_to(0.2);
+        game.paddles[1].update(Duration::from_secs(1));
+        assert!(game.paddles[1].offset() < 0.5);
+    }
+
+    #[test]
+    fn served_balls_head_different_ways() {
+        let mut game = started(MatchConfig {
+            balls: MatchConfig::MAX_BALLS,
+            ..MatchConfig::default()
+        });
+        game.serve();
+        assert_eq!(game.balls.len(), MatchConfig::MAX_BALLS);
+        for (index, ball) in game.balls.iter().enumerate() {
+            assert_eq!(ball.pos, Point::CENTER);
+            assert!(game.balls[..index]
+                .iter()
+                .all(|other| other.vel != ball.vel));
+        }
+    }
+
+    #[test]
+    fn practice_runs() {
+        let mut game = started(MatchConfig {
+            mode: Mode::Practice,
+            ..MatchConfig::default()
+        });
+        let mut events = game.subscribe();
+        // the ball speeds up with each return, up to a limit
+        game.rally = 5;
+        game.apply_ball_speed();
+        assert!((game.balls[0].speed - 1.5).abs() < 1e-6);
+        game.rally = 100;
+        game.apply_ball_speed();
+        assert_eq!(game.balls[0].speed, Game::MAX_PRACTICE_SPEED);
+
+        game.end_run(0);
+        game.rally = 3;
+        game.end_run(0);
+        assert_eq!(game.players[0].as_ref().unwrap().practice_best, 100);
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(GameEvent::RunEnded {
+                slot: 0,
+                returns: 100
+            })
+        );
+
+        // letting the ball past ends the run and serves again at the usual speed
+        game.balls[0] = Ball {
+            pos: Point::new(0.01, 0.1),
+            ..Ball::with_velocity(Velocity::new(-0.5, 0.0))
+        };
+        game.update();
+        assert_eq!(game.rally, 0);
+        assert_eq!(game.balls[0].speed, 1.0);
+        assert_eq!(game.balls[0].pos, Point::CENTER);
+    }
+
+    /// Two balls leaving through the same side in one update.
+    fn two_balls_past(game: &mut Game, x: f32, vel_x: f32) -> Vec<GameEvent> {
+        let mut events = game.subscribe();
+        game.balls = [0.1, 0.2]
+            .map(|y| Ball {
+                pos: Point::new(x, y),
+                ..Ball::with_velocity(Velocity::new(vel_x, 0.0))
+            })
+            .to_vec();
+        game.update();
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn two_balls_past_an_eliminated_team() {
+        let mut game = started(MatchConfig {
+            mode: Mode::FourPlayer,
+            balls: 2,
+            ..MatchConfig::default()
+        });
+        game.score[0] = 1;
+        let events = two_balls_past(&mut game, 0.01, -0.5);
+        let lost = events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::LifeLost { .. }))
+            .count();
+        assert_eq!(lost, 1);
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| **event == GameEvent::PlayerEliminated { team: 0 })
+                .count(),
+            1
+        );
+        assert_eq!(game.score, [0, 5, 5, 5]);
+    }
+
+    #[test]
+    fn two_balls_past_on_match_point() {
+        let mut game = started(MatchConfig {
+            balls: 2,
+            ..MatchConfig::default()
+        });
+        game.score = vec![Game::WINNING_SCORE - 1, 0];
+        let events = two_balls_past(&mut game, 0.99, 0.5);
+        let ended = events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::MatchEnded { .. }))
+            .count();
+        assert_eq!(ended, 1);
+        // the second ball doesn't carry a point into the next match
+        assert_eq!(game.score, [0, 0]);
+    }
+
+    #[test]
+    fn court_is_letterboxed() {
+        // square-ish cells are twice as tall as they are wide, so 4:3 is 8:3 in cells
+        let court = Game::court_area(Rect::new(0, 0, 80, 24), 2.0);
+        assert_eq!(court, Rect::new(8, 0, 64, 24));
+        let court = Game::court_area(Rect::new(0, 0, 250, 60), 2.0);
+        assert_eq!(court, Rect::new(45, 0, 160, 60));
+        // a tall terminal leaves space above and below instead
+        let court = Game::court_area(Rect::new(0, 0, 80, 60), 2.0);
+        assert_eq!(court, Rect::new(0, 15, 80, 30));
+    }
+
+    #[test]
+    fn too_small_threshold() {
+        let needed = Size::new(40, 15);
+        assert!(!Game::is_too_small(
+            Rect::new(0, 0, needed.width, needed.height),
+            2.0
+        ));
+        assert!(Game::is_too_small(
+            Rect::new(0, 0, needed.width - 1, 50),
+            2.0
+        ));
+        assert!(Game::is_too_small(
+            Rect::new(0, 0, 200, needed.height - 1),
+            2.0
+        ));
+
+        let area = Rect::new(0, 0, 60, 10);
+        let mut buf = Buffer::empty(area);
+        render_too_small(area, 2.0, &mut buf);
+        let text: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(text.contains("Resize to at least 40x15 (currently 60x10)"));
+
+        // with square cells a court 40 columns wide is 30 rows tall
+        let mut buf = Buffer::empty(area);
+        render_too_small(area, 1.0, &mut buf);
+        let text: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(text.contains("Resize to at least 40x30 (currently 60x10)"));
+        assert!(!Game::is_too_small(Rect::new(0, 0, 40, 30), 1.0));
+        assert!(Game::is_too_small(Rect::new(0, 0, 40, 29), 1.0));
     }
 }
@@ -0,0 +1,649 @@
+use std::{collections::HashMap, fmt::Write, ops::Range};
+
+use ratatui::layout::{Rect, Size};
+
+/// A protocol for drawing images in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    /// The kitty graphics protocol, which keeps uploaded images in the terminal so that moving
+    /// them only needs a short placement command.
+    Kitty,
+    /// DEC sixel graphics, which needs the whole image to be sent again whenever it changes.
+    Sixel,
+}
+
+pub type Rgb = (u8, u8, u8);
+
+/// A solid colored rectangle, in pixels from the top left of the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shape {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub color: Rgb,
+}
+
+/// What to draw with graphics: a background with some rectangles on top of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene {
+    /// The cells covered by the scene.
+    pub area: Rect,
+    /// The size of each cell in pixels.
+    pub cell_size: Size,
+    pub background: Rgb,
+    /// The shapes in the scene, drawn in order.
+    pub shapes: Vec<Shape>,
+    /// Shapes behind the others that are also drawn with characters, such as the net, so they
+    /// only need to be drawn by renderers that replace the cells underneath the scene.
+    pub backdrop: Vec<Shape>,
+    /// The cells covered by overlays such as notifications, where nothing is drawn so the
+    /// overlays stay visible.
+    pub covered: Vec<Rect>,
+}
+
+impl Scene {
+    /// The size of the scene in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (
+            u32::from(self.area.width) * u32::from(self.cell_size.width),
+            u32::from(self.area.height) * u32::from(self.cell_size.height),
+        )
+    }
+
+    /// The cells the shape is drawn over.
+    fn cells(&self, shape: &Shape) -> Rect {
+        let (cell_width, cell_height) = (
+            u32::from(self.cell_size.width.max(1)),
+            u32::from(self.cell_size.height.max(1)),
+        );
+        let (left, top) = (shape.x / cell_width, shape.y / cell_height);
+        let right = (shape.x + shape.width).div_ceil(cell_width);
+        let bottom = (shape.y + shape.height).div_ceil(cell_height);
+        Rect::new(
+            self.area.x + left as u16,
+            self.area.y + top as u16,
+            (right - left) as u16,
+            (bottom - top) as u16,
+        )
+    }
+
+    /// Whether the shape is under an overlay.
+    fn is_covered(&self, shape: &Shape) -> bool {
+        let cells = self.cells(shape);
+        self.covered.iter().any(|covered| covered.intersects(cells))
+    }
+
+    /// The runs of columns in the given row of the scene that aren't under an overlay.
+    fn uncovered_columns(&self, row: u32) -> Vec<Range<u32>> {
+        let y = u32::from(self.area.y) + row;
+        let all_columns = 0..u32::from(self.area.width);
+        let mut columns = vec![all_columns];
+        for covered in &self.covered {
+            if !(u32::from(covered.top())..u32::from(covered.bottom())).contains(&y) {
+                continue;
+            }
+            let left = u32::from(covered.left().saturating_sub(self.area.x));
+            let right = u32::from(covered.right().saturating_sub(self.area.x));
+            columns = columns
+                .into_iter()
+                .flat_map(|run| [run.start..run.end.min(left), run.start.max(right)..run.end])
+                .filter(|run| !run.is_empty())
+                .collect();
+        }
+        columns
+    }
+
+    fn color_at(&self, x: u32, y: u32) -> Rgb {
+        self.backdrop
+            .iter()
+            .chain(&self.shapes)
+            .rev()
+            .find(|shape| {
+                (shape.x..shape.x + shape.width).contains(&x)
+                    && (shape.y..shape.y + shape.height).contains(&y)
+            })
+            .map_or(self.background, |shape| shape.color)
+    }
+}
+
+/// Turns [`Scene`]s into the escape sequences that draw them on one client's terminal.
+///
+/// The scene is drawn over cells that the character renderer leaves blank, after each frame.
+/// Nothing is drawn under overlays, while the images already sent are kept for when the overlays
+/// are gone.
+#[derive(Debug)]
+pub struct GraphicsRenderer {
+    protocol: GraphicsProtocol,
+    // The last scene drawn, so unchanged frames aren't sent again
+    last_scene: Option<Scene>,
+    // The kitty images already uploaded, one per color
+    images: HashMap<Rgb, KittyImage>,
+    // Ids of deleted kitty images, for reuse
+    free_ids: Vec<u32>,
+    // Counts the frames drawn with kitty graphics, to find the least recently used images
+    frame: u64,
+    // The image id of each kitty placement in the last frame, indexed by placement id - 1
+    placements: Vec<u32>,
+}
+
+/// A solid colored image uploaded to a kitty terminal, which is cropped to the size of each shape
+/// it is placed for.
+#[derive(Debug, Clone, Copy)]
+struct KittyImage {
+    id: u32,
+    width: u32,
+    height: u32,
+    // The last frame the image was placed in
+    last_used: u64,
+}
+
+impl GraphicsRenderer {
+    // Image ids below this are left for the capability query
+    const FIRST_IMAGE_ID: u32 = 100;
+    // The largest payload allowed in a single kitty graphics command
+    const KITTY_CHUNK_LEN: usize = 4096;
+    // The most images kept in the terminal before the least recently used are deleted
+    const MAX_KITTY_IMAGES: usize = 32;
+
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol,
+            last_scene: None,
+            images: HashMap::new(),
+            free_ids: Vec::new(),
+            frame: 0,
+            placements: Vec::new(),
+        }
+    }
+
+    /// The escape sequences that draw the scene, or nothing if it hasn't changed since the last
+    /// call.
+    pub fn draw(&mut self, scene: Scene) -> String {
+        if self.last_scene.as_ref() == Some(&scene) {
+            return String::new();
+        }
+        let output = match self.protocol {
+            GraphicsProtocol::Kitty => self.draw_kitty(&scene),
+            GraphicsProtocol::Sixel => changed_rows(&scene, self.last_scene.as_ref())
+                .into_iter()
+                .flat_map(|rows| {
+                    scene
+                        .uncovered_columns(rows.start)
+                        .into_iter()
+                        .map(move |columns| (rows.clone(), columns))
+                })
+                .map(|(rows, columns)| draw_sixel(&scene, rows, columns))
+                .collect(),
+        };
+        self.last_scene = Some(scene);
+        output
+    }
+
+    /// The escape sequences that remove anything drawn, before going back to drawing with
+    /// characters.
+    ///
+    /// Sixel images are part of the screen contents, so they are removed by redrawing the cells
+    /// over them instead.
+    pub fn clear(&mut self) -> String {
+        self.last_scene = None;
+        self.placements.clear();
+        match self.protocol {
+            GraphicsProtocol::Kitty => "\x1b_Ga=d,d=a,q=2\x1b\\".to_string(),
+            GraphicsProtocol::Sixel => String::new(),
+        }
+    }
+
+    /// Draw the background as cell colors and each shape as a placement of a solid image.
+    ///
+    /// Each color is uploaded once, as large as the largest shape drawn in it, and cropped to the
+    /// size of each shape when it is placed. Moving a shape replaces its placement, so only a few
+    /// bytes are sent per frame.
+    fn draw_kitty(&mut self, scene: &Scene) -> String {
+        let mut output = String::new();
+        self.frame += 1;
+        let shapes: Vec<_> = scene
+            .shapes
+            .iter()
+            .filter(|shape| shape.width > 0 && shape.height > 0 && !scene.is_covered(shape))
+            .collect();
+        for shape in &shapes {
+            if let Some(image) = self.images.get_mut(&shape.color) {
+                image.last_used = self.frame;
+            }
+        }
+        self.evict_kitty_images(&mut output, &shapes);
+        let image_ids: Vec<_> = shapes
+            .iter()
+            .map(|shape| self.kitty_image(&mut output, shape))
+            .collect();
+
+        let (cell_width, cell_height) = (
+            u32::from(scene.cell_size.width.max(1)),
+            u32::from(scene.cell_size.height.max(1)),
+        );
+        for ((placement_id, shape), &image_id) in (1..).zip(&shapes).zip(&image_ids) {
+            // a placement only replaces the previous one with the same image
+            if let Some(&old) = self.placements.get(placement_id as usize - 1) {
+                if old != image_id {
+                    delete_kitty_placement(&mut output, old, placement_id);
+                }
+            }
+            let column = u32::from(scene.area.x) + shape.x / cell_width;
+            let row = u32::from(scene.area.y) + shape.y / cell_height;
+            let _ = write!(
+                output,
+                "\x1b[{};{}H\x1b_Ga=p,i={image_id},p={placement_id},X={},Y={},w={},h={},C=1,q=2\x1b\\",
+                row + 1,
+                column + 1,
+                shape.x % cell_width,
+                shape.y % cell_height,
+                shape.width,
+                shape.height,
+            );
+        }
+        for (placement_id, &old) in (1..).zip(&self.placements).skip(image_ids.len()) {
+            delete_kitty_placement(&mut output, old, placement_id);
+        }
+        self.placements = image_ids;
+        output
+    }
+
+    /// The id of the image for the shape's color, uploading it first if there isn't one large
+    /// enough yet.
+    fn kitty_image(&mut self, output: &mut String, shape: &Shape) -> u32 {
+        let image = match self.images.get(&shape.color) {
+            Some(image) if image.width >= shape.width && image.height >= shape.height => {
+                return image.id
+            }
+            // uploading an image with the same id replaces it
+            Some(image) => KittyImage {
+                width: image.width.max(shape.width),
+                height: image.height.max(shape.height),
+                ..*image
+            },
+            None => KittyImage {
+                id: self.free_ids.pop().unwrap_or_else(|| {
+                    Self::FIRST_IMAGE_ID + (self.images.len() + self.free_ids.len()) as u32
+                }),
+                width: shape.width,
+                height: shape.height,
+                last_used: self.frame,
+            },
+        };
+        upload_kitty(output, &image, shape.color);
+        self.images.insert(shape.color, image);
+        image.id
+    }
+
+    /// Delete the least recently used images from the terminal until there is room for the new
+    /// colors in the frame, so colors that are no longer drawn don't pile up.
+    fn evict_kitty_images(&mut self, output: &mut String, shapes: &[&Shape]) {
+        let mut new_colors: Vec<_> = shapes
+            .iter()
+            .map(|shape| shape.color)
+            .filter(|color| !self.images.contains_key(color))
+            .collect();
+        new_colors.sort_unstable();
+        new_colors.dedup();
+        while self.images.len() + new_colors.len() > Self::MAX_KITTY_IMAGES {
+            let Some((&color, image)) = self
+                .images
+                .iter()
+                .filter(|(_, image)| image.last_used < self.frame)
+                .min_by_key(|(_, image)| image.last_used)
+            else {
+                break;
+            };
+            // `I` frees the image data as well as removing its placements
+            let _ = write!(output, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", image.id);
+            self.free_ids.push(image.id);
+            self.images.remove(&color);
+        }
+    }
+}
+
+fn delete_kitty_placement(output: &mut String, image_id: u32, placement_id: u32) {
+    let _ = write!(
+        output,
+        "\x1b_Ga=d,d=i,i={image_id},p={placement_id},q=2\x1b\\"
+    );
+}
+
+/// Upload a solid colored image, split into chunks.
+fn upload_kitty(output: &mut String, image: &KittyImage, (r, g, b): Rgb) {
+    let KittyImage {
+        id, width, height, ..
+    } = *image;
+    let pixels: Vec<u8> = [r, g, b].repeat((width * height) as usize);
+    let data = base64(&pixels);
+    let chunks: Vec<_> = data
+        .as_bytes()
+        .chunks(GraphicsRenderer::KITTY_CHUNK_LEN)
+        .collect();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if index == 0 {
+            let _ = write!(
+                output,
+                "\x1b_Ga=t,f=24,i={id},s={width},v={height},q=2,m={more};{chunk}\x1b\\",
+            );
+        } else {
+            let _ = write!(output, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+}
+
+/// The runs of cell rows that differ from the last scene, which are all the rows if the scene
+/// has moved or there is no last scene. The rows in each run are covered by the same overlays.
+///
+/// Shapes are drawn in order, so pixels can only change where a shape differs from the one drawn
+/// at the same position in the list. Rows also need drawing again where the overlays over them
+/// have changed.
+fn changed_rows(scene: &Scene, last: Option<&Scene>) -> Vec<Range<u32>> {
+    let rows = u32::from(scene.area.height);
+    let uncovered: Vec<_> = (0..rows).map(|row| scene.uncovered_columns(row)).collect();
+    let changed = match last.filter(|last| {
+        (last.area, last.cell_size, last.background)
+            == (scene.area, scene.cell_size, scene.background)
+    }) {
+        Some(last) => changed_shape_rows(scene, last)
+            .into_iter()
+            .zip(&uncovered)
+            .enumerate()
+            .map(|(row, (changed, uncovered))| {
+                changed || *uncovered != last.uncovered_columns(row as u32)
+            })
+            .collect(),
+        None => vec![true; rows as usize],
+    };
+    let mut runs: Vec<Range<u32>> = Vec::new();
+    for row in (0..rows).filter(|&row| changed[row as usize]) {
+        match runs.last_mut() {
+            Some(run)
+                if run.end == row && uncovered[run.start as usize] == uncovered[row as usize] =>
+            {
+                run.end += 1
+            }
+            _ => runs.push(row..row + 1),
+        }
+    }
+    runs
+}
+
+/// Whether each cell row has a shape that differs from the last scene.
+fn changed_shape_rows(scene: &Scene, last: &Scene) -> Vec<bool> {
+    let rows = u32::from(scene.area.height);
+    let cell_height = u32::from(scene.cell_size.height.max(1));
+    let mut changed = vec![false; rows as usize];
+    for (new, old) in [
+        (&scene.backdrop, &last.backdrop),
+        (&scene.shapes, &last.shapes),
+    ] {
+        for index in 0..new.len().max(old.len()) {
+            let (new, old) = (new.get(index), old.get(index));
+            if new == old {
+                continue;
+            }
+            for shape in new.into_iter().chain(old) {
+                let first = shape.y / cell_height;
+                let last = (shape.y + shape.height).div_ceil(cell_height).min(rows);
+                for row in first..last {
+                    changed[row as usize] = true;
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Encode the given cells of the scene as a sixel image placed over them.
+///
+/// Each band of six pixel rows is drawn once per color, with runs of the same sixel run-length
+/// encoded. Scenes are mostly background, so this stays small.
+fn draw_sixel(scene: &Scene, rows: Range<u32>, columns: Range<u32>) -> String {
+    let (scene_width, scene_height) = scene.size();
+    let (cell_width, cell_height) = (
+        u32::from(scene.cell_size.width),
+        u32::from(scene.cell_size.height),
+    );
+    let (left, top) = (columns.start * cell_width, rows.start * cell_height);
+    let width = (columns.end * cell_width)
+        .min(scene_width)
+        .saturating_sub(left);
+    let height = (rows.end * cell_height)
+        .min(scene_height)
+        .saturating_sub(top);
+    let mut palette = vec![scene.background];
+    for shape in scene.backdrop.iter().chain(&scene.shapes) {
+        if !palette.contains(&shape.color) {
+            palette.push(shape.color);
+        }
+    }
+
+    let mut output = format!(
+        "\x1b[{};{}H\x1bPq\"1;1;{width};{height}",
+        u32::from(scene.area.y) + rows.start + 1,
+        u32::from(scene.area.x) + columns.start + 1
+    );
+    for (index, (r, g, b)) in palette.iter().enumerate() {
+        // sixel colors are percentages
+        let percent = |c: u8| u32::from(c) * 100 / 255;
+        let _ = write!(
+            output,
+            "#{index};2;{};{};{}",
+            percent(*r),
+            percent(*g),
+            percent(*b)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        if band > 0 {
+            // move down to the next band
+            output.push('-');
+        }
+        let rows = band..(band + 6).min(height);
+        let colors: Vec<Vec<Rgb>> = rows
+            .clone()
+            .map(|y| {
+                (0..width)
+                    .map(|x| scene.color_at(left + x, top + y))
+                    .collect()
+            })
+            .collect();
+        for (index, color) in palette.iter().enumerate() {
+            let sixels: Vec<u8> = (0..width as usize)
+                .map(|x| {
+                    (0..colors.len())
+                        .filter(|&row| colors[row][x] == *color)
+                        .fold(0, |bits, row| bits | 1 << row)
+                })
+                .collect();
+            if sixels.iter().all(|&bits| bits == 0) {
+                continue;
+            }
+            let _ = write!(output, "#{index}");
+            push_sixel_runs(&mut output, &sixels);
+            // return to the start of the band for the next color
+            output.push('$');
+        }
+    }
+    output.push_str("\x1b\\");
+    output
+}
+
+fn push_sixel_runs(output: &mut String, sixels: &[u8]) {
+    let mut start = 0;
+    while start < sixels.len() {
+        let bits = sixels[start];
+        let len = sixels[start..]
+            .iter()
+            .take_while(|&&other| other == bits)
+            .count();
+        let c = char::from(0x3f + bits);
+        if len > 3 {
+            let _ = write!(output, "!{len}{c}");
+        } else {
+            output.extend(std::iter::repeat_n(c, len));
+        }
+        start += len;
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_base64() {
+        assert_eq!(base64(b"pong"), "cG9uZw==");
+        assert_eq!(base64(b"ball"), "YmFsbA==");
+        assert_eq!(base64(b"net"), "bmV0");
+    }
+
+    #[test]
+    fn sixel_runs() {
+        let scene = Scene {
+            area: Rect::new(0, 0, 1, 1),
+            cell_size: Size::new(8, 6),
+            background: (0, 0, 0),
+            shapes: vec![Shape {
+                x: 2,
+                y: 0,
+                width: 1,
+                height: 6,
+                color: (255, 255, 255),
+            }],
+            backdrop: Vec::new(),
+            covered: Vec::new(),
+        };
+        assert_eq!(
+            draw_sixel(&scene, 0..1, 0..1),
+            "\x1b[1;1H\x1bPq\"1;1;8;6#0;2;0;0;0#1;2;100;100;100#0~~?!5~$#1??~!5?$\x1b\\"
+        );
+    }
+
+    fn shape(x: u32, width: u32, color: Rgb) -> Shape {
+        Shape {
+            x,
+            y: 0,
+            width,
+            height: 4,
+            color,
+        }
+    }
+
+    #[test]
+    fn kitty_images_per_color() {
+        let mut renderer = GraphicsRenderer::new(GraphicsProtocol::Kitty);
+        let mut scene = Scene {
+            area: Rect::new(0, 0, 10, 10),
+            cell_size: Size::new(8, 16),
+            background: (0, 0, 0),
+            shapes: vec![shape(0, 4, (255, 0, 0)), shape(10, 2, (255, 0, 0))],
+            backdrop: Vec::new(),
+            covered: Vec::new(),
+        };
+        let output = renderer.draw(scene.clone());
+        assert_eq!(output.matches("a=t").count(), 1);
+        assert!(output.contains("a=p,i=100,p=2,X=2,Y=0,w=2,h=4"));
+
+        // growing a shape uploads its color again, larger
+        scene.shapes[1].width = 6;
+        let output = renderer.draw(scene.clone());
+        assert!(output.contains("a=t,f=24,i=100,s=6,v=4"));
+        assert!(!output.contains("a=d"));
+
+        // colors that are no longer drawn are deleted once there are too many
+        for step in 0..GraphicsRenderer::MAX_KITTY_IMAGES as u8 {
+            scene.shapes[0].color = (0, step, 0);
+            renderer.draw(scene.clone());
+        }
+        assert_eq!(renderer.images.len(), GraphicsRenderer::MAX_KITTY_IMAGES);
+        scene.shapes[0].color = (0, 0, 255);
+        let output = renderer.draw(scene);
+        assert!(output.contains("a=d,d=I,i=102"));
+        assert!(output.contains("a=t,f=24,i=102"));
+        assert_eq!(renderer.images.len(), GraphicsRenderer::MAX_KITTY_IMAGES);
+    }
+
+    #[test]
+    fn sixel_changed_rows() {
+        let mut scene = Scene {
+            area: Rect::new(0, 0, 10, 10),
+            cell_size: Size::new(8, 6),
+            background: (0, 0, 0),
+            shapes: vec![shape(0, 4, (255, 0, 0)), shape(8, 4, (0, 255, 0))],
+            backdrop: Vec::new(),
+            covered: Vec::new(),
+        };
+        let all_rows = 0..10;
+        assert_eq!(changed_rows(&scene, None), [all_rows]);
+        let last = scene.clone();
+        scene.shapes[1].y = 16;
+        assert_eq!(changed_rows(&scene, Some(&last)), [0..1, 2..4]);
+        assert!(draw_sixel(&scene, 2..4, 0..10).starts_with("\x1b[3;1H\x1bPq\"1;1;80;12"));
+    }
+
+    #[test]
+    fn nothing_drawn_under_overlays() {
+        let mut renderer = GraphicsRenderer::new(GraphicsProtocol::Kitty);
+        let mut scene = Scene {
+            area: Rect::new(2, 1, 10, 10),
+            cell_size: Size::new(8, 16),
+            background: (0, 0, 0),
+            shapes: vec![shape(0, 4, (255, 0, 0)), shape(40, 4, (0, 255, 0))],
+            backdrop: Vec::new(),
+            covered: Vec::new(),
+        };
+        renderer.draw(scene.clone());
+
+        // the first shape's placement is removed, but its image is kept for later
+        scene.covered = vec![Rect::new(0, 1, 4, 1)];
+        let output = renderer.draw(scene.clone());
+        assert!(output.contains("a=d,d=i,i=100,p=1"));
+        assert!(output.contains("a=p,i=101,p=1"));
+        assert!(!output.contains("a=t"));
+        assert_eq!(renderer.images.len(), 2);
+        scene.covered.clear();
+        let output = renderer.draw(scene.clone());
+        assert!(output.contains("a=p,i=100,p=1"));
+        assert!(!output.contains("a=t"));
+
+        // sixels are only drawn beside the overlay, and the rows under it are drawn again when
+        // it's gone
+        let mut renderer = GraphicsRenderer::new(GraphicsProtocol::Sixel);
+        renderer.draw(scene.clone());
+        scene.covered = vec![Rect::new(4, 3, 3, 2)];
+        let all_columns = 0..10;
+        assert_eq!(scene.uncovered_columns(1), [all_columns]);
+        assert_eq!(scene.uncovered_columns(2), [0..2, 5..10]);
+        let output = renderer.draw(scene.clone());
+        assert_eq!(output.matches("\x1bPq").count(), 2);
+        assert!(output.starts_with("\x1b[4;3H\x1bPq\"1;1;16;32"));
+        assert!(output.contains("\x1b[4;8H\x1bPq\"1;1;40;32"));
+        scene.covered.clear();
+        let output = renderer.draw(scene);
+        assert!(output.starts_with("\x1b[4;3H\x1bPq\"1;1;80;32"));
+    }
+}
@@ -0,0 +1,79 @@
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Clear},
+};
+
+use crate::{
+    capabilities::Capabilities,
+    keymap::{key_name, Action, Keymap},
+};
+
+/// An overlay listing the controls from a player's keymap.
+pub struct HelpOverlay<'a> {
+    keymap: &'a Keymap,
+    capabilities: Capabilities,
+}
+
+impl<'a> HelpOverlay<'a> {
+    pub fn new(keymap: &'a Keymap, capabilities: Capabilities) -> Self {
+        Self {
+            keymap,
+            capabilities,
+        }
+    }
+
+    /// The part of the given area the overlay is drawn over.
+    pub fn area(area: Rect) -> Rect {
+        // a line for each action, the mouse and the note about highlighting, and the borders
+        let height = Action::ALL.len() as u16 + 4;
+        let [area] = Layout::horizontal([Constraint::Length(44)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        area
+    }
+}
+
+impl Widget for HelpOverlay<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut lines: Vec<Line> = Action::ALL
+            .iter()
+            .map(|&action| {
+                let keys: Vec<_> = self
+                    .keymap
+                    .keys(action)
+                    .iter()
+                    .copied()
+                    .map(key_name)
+                    .collect();
+                Line::from(vec![
+                    Span::raw(format!(" {:<18} ", action.description())),
+                    Span::raw(keys.join(" ")).bold(),
+                ])
+            })
+            .collect();
+        lines.push(Line::from(vec![
+            Span::raw(format!(" {:<18} ", "Move paddle to")),
+            Span::raw("Mouse").bold(),
+        ]));
+        lines.push(Line::from(" Your paddle and name are highlighted").dim());
+
+        let area = Self::area(area);
+        Clear.render(area, buf);
+        let capabilities = self.capabilities;
+        let block = Block::bordered()
+            .border_set(capabilities.border_set())
+            .title("Controls")
+            .title_alignment(Alignment::Center)
+            .style((
+                capabilities.color(Color::White),
+                capabilities.color(Color::Black),
+            ));
+        let inner = block.inner(area);
+        block.render(area, buf);
+        Text::from(lines).render(inner, buf);
+    }
+}
@@ -0,0 +1,555 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, MouseButton,
+    MouseEvent, MouseEventKind,
+};
+
+/// An input event decoded from the bytes a client sends over the SSH channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    /// A mouse event reported using SGR mouse encoding (`CSI < b ; x ; y M`).
+    ///
+    /// The column and row are zero-based.
+    Mouse(MouseEvent),
+    /// The terminal's reply to a kitty keyboard protocol query (`CSI ? u`).
+    ///
+    /// Terminals that don't support the protocol never reply.
+    KeyboardEnhancementFlags(KeyboardEnhancementFlags),
+    /// The terminal's reply to an XTVERSION query (`CSI > 0 q`), e.g. `kitty(0.35.2)`.
+    TerminalVersion(String),
+    /// The terminal's reply to a primary device attributes query (`CSI c`), listing the features
+    /// it supports. `4` means sixel graphics.
+    ///
+    /// Almost all terminals reply, so this also marks the end of the replies to earlier queries.
+    DeviceAttributes(Vec<u16>),
+    /// The terminal's reply to a kitty graphics protocol query, which is `true` if it can display
+    /// images sent with the protocol.
+    KittyGraphics(bool),
+    /// The terminal's reply to a device status report query (`CSI 5 n`), meaning it is working.
+    ///
+    /// Terminals answer straight away, which is used to measure the latency to the client.
+    StatusReport,
+}
+
+/// Decodes raw terminal input into [`InputEvent`]s.
+///
+/// SSH delivers input in arbitrary chunks, so an escape sequence can be split across calls to
+/// [`InputDecoder::decode`]. Incomplete sequences are buffered until the rest arrives.
+///
+/// A lone escape could be the escape key or the start of a sequence whose rest is still on its
+/// way, so it is only taken as the escape key once [`InputDecoder::ESCAPE_TIMEOUT`] passes without
+/// anything following it (see [`InputDecoder::flush_escape`]).
+#[derive(Debug, Default)]
+pub struct InputDecoder {
+    buffer: Vec<u8>,
+    // When the buffer was left holding a lone escape
+    escape_since: Option<Instant>,
+}
+
+enum Parsed {
+    Event(usize, InputEvent),
+    Skip(usize),
+    Incomplete,
+}
+
+impl InputDecoder {
+    // Give up on escape sequences longer than this rather than buffering forever
+    const MAX_SEQUENCE_LEN: usize = 64;
+
+    /// How long to wait for the rest of a sequence after a lone escape.
+    pub const ESCAPE_TIMEOUT: Duration = Duration::from_millis(100);
+
+    pub fn decode(&mut self, data: &[u8], now: Instant) -> Vec<InputEvent> {
+        self.buffer.extend_from_slice(data);
+        let mut events = Vec::new();
+        let mut start = 0;
+        while start < self.buffer.len() {
+            match parse_event(&self.buffer[start..]) {
+                Parsed::Event(len, event) => {
+                    events.push(event);
+                    start += len;
+                }
+                Parsed::Skip(len) => start += len,
+                Parsed::Incomplete => break,
+            }
+        }
+        self.buffer.drain(..start);
+        self.escape_since = self.has_pending_escape().then_some(now);
+        events
+    }
+
+    /// Whether the input ended with a lone escape, which needs flushing with
+    /// [`InputDecoder::flush_escape`] if nothing else arrives.
+    pub fn has_pending_escape(&self) -> bool {
+        self.buffer == b"\x1b"
+    }
+
+    /// Take a lone escape as the escape key once the timeout has passed without the rest of a
+    /// sequence arriving.
+    pub fn flush_escape(&mut self, now: Instant) -> Option<InputEvent> {
+        let since = self.escape_since?;
+        if !self.has_pending_escape() || now - since < Self::ESCAPE_TIMEOUT {
+            return None;
+        }
+        self.buffer.clear();
+        self.escape_since = None;
+        Some(InputEvent::Key(KeyEvent::new(
+            KeyCode::Esc,
+            KeyModifiers::NONE,
+        )))
+    }
+}
+
+fn parse_event(bytes: &[u8]) -> Parsed {
+    match bytes {
+        // wait for the rest of the sequence, or for the escape key timeout
+        [b'\x1b'] => Parsed::Incomplete,
+        [b'\x1b', b'[', rest @ ..] => parse_csi(rest).map_len(|len| len + 2),
+        [b'\x1b', b'P', rest @ ..] => parse_dcs(rest).map_len(|len| len + 2),
+        [b'\x1b', b'_', rest @ ..] => parse_apc(rest).map_len(|len| len + 2),
+        [b'\x1b', b'O'] => Parsed::Incomplete,
+        [b'\x1b', b'O', b, ..] => match b {
+            b'A' => key(3, KeyCode::Up, KeyModifiers::NONE),
+            b'B' => key(3, KeyCode::Down, KeyModifiers::NONE),
+            b'C' => key(3, KeyCode::Right, KeyModifiers::NONE),
+            b'D' => key(3, KeyCode::Left, KeyModifiers::NONE),
+            _ => Parsed::Skip(3),
+        },
+        // an escape followed by anything else is a bare escape key press
+        [b'\x1b', ..] => key(1, KeyCode::Esc, KeyModifiers::NONE),
+        [b'\r' | b'\n', ..] => key(1, KeyCode::Enter, KeyModifiers::NONE),
+        [b'\t', ..] => key(1, KeyCode::Tab, KeyModifiers::NONE),
+        [b'\x7f' | b'\x08', ..] => key(1, KeyCode::Backspace, KeyModifiers::NONE),
+        [b @ 0x01..=0x1a, ..] => key(
+            1,
+            KeyCode::Char((b - 1 + b'a') as char),
+            KeyModifiers::CONTROL,
+        ),
+        [b, ..] if b.is_ascii_control() => Parsed::Skip(1),
+        [b, ..] => {
+            let len = match b.leading_ones() {
+                0 => 1,
+                2..=4 => b.leading_ones() as usize,
+                _ => return Parsed::Skip(1),
+            };
+            if bytes.len() < len {
+                return Parsed::Incomplete;
+            }
+            match std::str::from_utf8(&bytes[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => key(len, KeyCode::Char(c), KeyModifiers::NONE),
+                None => Parsed::Skip(1),
+            }
+        }
+        [] => Parsed::Incomplete,
+    }
+}
+
+/// Parse a CSI sequence (the bytes following `ESC [`).
+///
+/// Handles legacy cursor keys (`CSI A`), xterm modified keys (`CSI 1;5A`, `CSI 3~`) and the kitty
+/// keyboard protocol (`CSI 119;1:3u`), where the modifier parameter may carry an event type.
+fn parse_csi(bytes: &[u8]) -> Parsed {
+    let Some(end) = bytes.iter().position(|b| (0x40..=0x7e).contains(b)) else {
+        return if bytes.len() > InputDecoder::MAX_SEQUENCE_LEN {
+            Parsed::Skip(bytes.len())
+        } else {
+            Parsed::Incomplete
+        };
+    };
+    let len = end + 1;
+    let Ok(params) = std::str::from_utf8(&bytes[..end]) else {
+        return Parsed::Skip(len);
+    };
+    let terminator = bytes[end];
+
+    if let Some(flags) = params.strip_prefix('?') {
+        return match (terminator, flags.parse()) {
+            (b'u', Ok(flags)) => Parsed::Event(
+                len,
+                InputEvent::KeyboardEnhancementFlags(KeyboardEnhancementFlags::from_bits_truncate(
+                    flags,
+                )),
+            ),
+            (b'c', _) => Parsed::Event(
+                len,
+                InputEvent::DeviceAttributes(
+                    flags.split(';').filter_map(|n| n.parse().ok()).collect(),
+                ),
+            ),
+            _ => Parsed::Skip(len),
+        };
+    }
+
+    if let Some(mouse) = params.strip_prefix('<') {
+        return match (terminator, parse_mouse(mouse, terminator == b'm')) {
+            (b'M' | b'm', Some(event)) => Parsed::Event(len, InputEvent::Mouse(event)),
+            _ => Parsed::Skip(len),
+        };
+    }
+
+    if terminator == b'n' {
+        return match params {
+            "0" => Parsed::Event(len, InputEvent::StatusReport),
+            _ => Parsed::Skip(len),
+        };
+    }
+
+    let mut fields = params.split(';');
+    // the key code may be followed by the shifted key code, e.g. `47:63` for `?`
+    let mut codes = fields.next().unwrap_or_default().split(':');
+    let number = codes.next().and_then(|n| n.parse::<u32>().ok());
+    let shifted = codes.next().and_then(|n| n.parse::<u32>().ok());
+    let (modifiers, kind) = fields
+        .next()
+        .map_or((KeyModifiers::NONE, KeyEventKind::Press), parse_modifiers);
+    let number = match shifted {
+        Some(shifted) if modifiers.contains(KeyModifiers::SHIFT) => Some(shifted),
+        _ => number,
+    };
+
+    let code = match (terminator, number) {
+        (b'A', _) => KeyCode::Up,
+        (b'B', _) => KeyCode::Down,
+        (b'C', _) => KeyCode::Right,
+        (b'D', _) => KeyCode::Left,
+        (b'H', _) => KeyCode::Home,
+        (b'F', _) => KeyCode::End,
+        (b'~', Some(1 | 7)) => KeyCode::Home,
+        (b'~', Some(2)) => KeyCode::Insert,
+        (b'~', Some(3)) => KeyCode::Delete,
+        (b'~', Some(4 | 8)) => KeyCode::End,
+        (b'~', Some(5)) => KeyCode::PageUp,
+        (b'~', Some(6)) => KeyCode::PageDown,
+        (b'u', Some(9)) => KeyCode::Tab,
+        (b'u', Some(13)) => KeyCode::Enter,
+        (b'u', Some(27)) => KeyCode::Esc,
+        (b'u', Some(127)) => KeyCode::Backspace,
+        (b'u', Some(n)) => match char::from_u32(n) {
+            Some(c) if !c.is_control() => KeyCode::Char(c),
+            _ => return Parsed::Skip(len),
+        },
+        _ => return Parsed::Skip(len),
+    };
+    Parsed::Event(
+        len,
+        InputEvent::Key(KeyEvent::new_with_kind(code, modifiers, kind)),
+    )
+}
+
+/// Parse a DCS sequence (the bytes following `ESC P`), terminated by `ESC \`.
+///
+/// The only DCS sequence we ask for is the reply to XTVERSION, which starts with `>|`.
+fn parse_dcs(bytes: &[u8]) -> Parsed {
+    let Some(end) = bytes.windows(2).position(|w| w == b"\x1b\\") else {
+        return if bytes.len() > InputDecoder::MAX_SEQUENCE_LEN {
+            Parsed::Skip(bytes.len())
+        } else {
+            Parsed::Incomplete
+        };
+    };
+    let len = end + 2;
+    match bytes[..end].strip_prefix(b">|") {
+        Some(version) => Parsed::Event(
+            len,
+            InputEvent::TerminalVersion(String::from_utf8_lossy(version).into_owned()),
+        ),
+        None => Parsed::Skip(len),
+    }
+}
+
+/// Parse an application program command (`ESC _ ... ESC \`), which the kitty graphics protocol
+/// uses for its replies, e.g. `Gi=31;OK`.
+fn parse_apc(bytes: &[u8]) -> Parsed {
+    let Some(end) = bytes.windows(2).position(|w| w == b"\x1b\\") else {
+        return if bytes.len() > InputDecoder::MAX_SEQUENCE_LEN {
+            Parsed::Skip(bytes.len())
+        } else {
+            Parsed::Incomplete
+        };
+    };
+    let len = end + 2;
+    match bytes[..end].strip_prefix(b"G") {
+        Some(reply) => {
+            let ok = reply.split(|&b| b == b';').nth(1) == Some(b"OK");
+            Parsed::Event(len, InputEvent::KittyGraphics(ok))
+        }
+        None => Parsed::Skip(len),
+    }
+}
+
+/// Parse the parameters of an SGR mouse report (`button;column;row`).
+///
+/// The low two bits of the button are the button number (3 means no button), and the higher bits
+/// flag shift (4), alt (8), ctrl (16), motion (32) and the scroll wheel (64). Releases are
+/// reported with a lowercase `m` terminator.
+fn parse_mouse(params: &str, release: bool) -> Option<MouseEvent> {
+    let mut fields = params.split(';').map(|field| field.parse::<u16>().ok());
+    let (cb, column, row) = (fields.next()??, fields.next()??, fields.next()??);
+    let button = match cb & 0b11 {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        _ => MouseButton::Right,
+    };
+    let kind = if cb & 64 != 0 {
+        match cb & 0b11 {
+            0 => MouseEventKind::ScrollUp,
+            1 => MouseEventKind::ScrollDown,
+            2 => MouseEventKind::ScrollLeft,
+            _ => MouseEventKind::ScrollRight,
+        }
+    } else if cb & 32 != 0 {
+        if cb & 0b11 == 3 {
+            MouseEventKind::Moved
+        } else {
+            MouseEventKind::Drag(button)
+        }
+    } else if release {
+        MouseEventKind::Up(button)
+    } else {
+        MouseEventKind::Down(button)
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    if cb & 4 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if cb & 8 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if cb & 16 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    Some(MouseEvent {
+        kind,
+        column: column.saturating_sub(1),
+        row: row.saturating_sub(1),
+        modifiers,
+    })
+}
+
+/// Parse a `modifiers[:event-type]` parameter.
+///
+/// The modifier value is one plus a bitmask of shift (1), alt (2), ctrl (4) and super (8). The
+/// event type is 1 for press, 2 for repeat and 3 for release.
+fn parse_modifiers(field: &str) -> (KeyModifiers, KeyEventKind) {
+    let mut parts = field.split(':');
+    let mask = parts
+        .next()
+        .and_then(|m| m.parse::<u8>().ok())
+        .map_or(0, |m| m.saturating_sub(1));
+    let mut modifiers = KeyModifiers::NONE;
+    if mask & 1 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if mask & 2 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if mask & 4 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    if mask & 8 != 0 {
+        modifiers |= KeyModifiers::SUPER;
+    }
+    let kind = match parts.next() {
+        Some("2") => KeyEventKind::Repeat,
+        Some("3") => KeyEventKind::Release,
+        _ => KeyEventKind::Press,
+    };
+    (modifiers, kind)
+}
+
+fn key(len: usize, code: KeyCode, modifiers: KeyModifiers) -> Parsed {
+    Parsed::Event(len, InputEvent::Key(KeyEvent::new(code, modifiers)))
+}
+
+impl Parsed {
+    fn map_len(self, f: impl FnOnce(usize) -> usize) -> Self {
+        match self {
+            Parsed::Event(len, event) => Parsed::Event(f(len), event),
+            Parsed::Skip(len) => Parsed::Skip(f(len)),
+            Parsed::Incomplete => Parsed::Incomplete,
+        }
+    }
+}
+
+/// Tracks whether a key is being held down.
+///
+/// Terminals that support the kitty keyboard protocol report key releases, so a key is held from
+/// its press until its release. Other terminals only send a press each time the OS repeats the
+/// key, so a key is considered held for a short time after each press instead.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum KeyHold {
+    #[default]
+    Released,
+    /// Held until a release event arrives.
+    Held,
+    /// Held until the deadline passes without another press.
+    Timed { last_press: Instant, until: Instant },
+}
+
+impl KeyHold {
+    // How long a single tap moves for when we can't see the release
+    const TAP_TIMEOUT: Duration = Duration::from_millis(80);
+    // How long a repeated press is held for. This needs to cover the OS key repeat interval plus
+    // any network jitter between repeats.
+    const REPEAT_TIMEOUT: Duration = Duration::from_millis(120);
+    // Presses closer together than this are treated as key repeats. This covers the OS delay
+    // before key repeat starts.
+    const REPEAT_DELAY: Duration = Duration::from_millis(700);
+
+    /// Record a press (or repeat) of the key.
+    ///
+    /// `release_expected` should be true when the terminal will report the key's release.
+    pub fn press(&mut self, now: Instant, release_expected: bool) {
+        if release_expected {
+            *self = KeyHold::Held;
+            return;
+        }
+        let timeout = match *self {
+            KeyHold::Timed { last_press, .. } if now - last_press < Self::REPEAT_DELAY => {
+                Self::REPEAT_TIMEOUT
+            }
+            _ => Self::TAP_TIMEOUT,
+        };
+        *self = KeyHold::Timed {
+            last_press: now,
+            until: now + timeout,
+        };
+    }
+
+    pub fn release(&mut self) {
+        *self = KeyHold::Released;
+    }
+
+    pub fn is_held(&self, now: Instant) -> bool {
+        match *self {
+            KeyHold::Released => false,
+            KeyHold::Held => true,
+            KeyHold::Timed { until, .. } => now < until,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8]) -> Vec<InputEvent> {
+        InputDecoder::default().decode(data, Instant::now())
+    }
+
+    fn key_event(code: KeyCode, kind: KeyEventKind) -> InputEvent {
+        InputEvent::Key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind))
+    }
+
+    #[test]
+    fn plain_characters() {
+        assert_eq!(
+            decode(b"ws"),
+            [
+                key_event(KeyCode::Char('w'), KeyEventKind::Press),
+                key_event(KeyCode::Char('s'), KeyEventKind::Press),
+            ]
+        );
+    }
+
+    #[test]
+    fn kitty_key_events() {
+        assert_eq!(
+            decode(b"\x1b[119u\x1b[119;1:2u\x1b[119;1:3u\x1b[1;1:3A"),
+            [
+                key_event(KeyCode::Char('w'), KeyEventKind::Press),
+                key_event(KeyCode::Char('w'), KeyEventKind::Repeat),
+                key_event(KeyCode::Char('w'), KeyEventKind::Release),
+                key_event(KeyCode::Up, KeyEventKind::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn sgr_mouse_events() {
+        assert_eq!(
+            decode(b"\x1b[<35;10;5M\x1b[<0;1;1m"),
+            [
+                InputEvent::Mouse(MouseEvent {
+                    kind: MouseEventKind::Moved,
+                    column: 9,
+                    row: 4,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                InputEvent::Mouse(MouseEvent {
+                    kind: MouseEventKind::Up(MouseButton::Left),
+                    column: 0,
+                    row: 0,
+                    modifiers: KeyModifiers::NONE,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn terminal_replies() {
+        assert_eq!(
+            decode(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4;22c\x1b[0n"),
+            [
+                InputEvent::KittyGraphics(true),
+                InputEvent::DeviceAttributes(vec![62, 4, 22]),
+                InputEvent::StatusReport,
+            ]
+        );
+    }
+
+    #[test]
+    fn split_sequence() {
+        let now = Instant::now();
+        let mut decoder = InputDecoder::default();
+        assert_eq!(decoder.decode(b"\x1b[119;1", now), []);
+        assert_eq!(
+            decoder.decode(b":3u", now),
+            [key_event(KeyCode::Char('w'), KeyEventKind::Release)]
+        );
+    }
+
+    #[test]
+    fn split_after_escape() {
+        let now = Instant::now();
+        let mut decoder = InputDecoder::default();
+        assert_eq!(decoder.decode(b"\x1b", now), []);
+        assert!(decoder.has_pending_escape());
+        assert_eq!(decoder.flush_escape(now), None);
+        assert_eq!(
+            decoder.decode(b"[A", now),
+            [key_event(KeyCode::Up, KeyEventKind::Press)]
+        );
+        assert_eq!(
+            decoder.flush_escape(now + InputDecoder::ESCAPE_TIMEOUT),
+            None
+        );
+
+        // nothing follows a lone escape key press
+        assert_eq!(decoder.decode(b"\x1b", now), []);
+        assert_eq!(
+            decoder.flush_escape(now + InputDecoder::ESCAPE_TIMEOUT),
+            Some(key_event(KeyCode::Esc, KeyEventKind::Press))
+        );
+        assert!(!decoder.has_pending_escape());
+    }
+
+    #[test]
+    fn key_hold_timeout() {
+        let now = Instant::now();
+        let mut hold = KeyHold::default();
+        hold.press(now, false);
+        assert!(hold.is_held(now));
+        assert!(!hold.is_held(now + Duration::from_millis(500)));
+
+        hold.press(now, true);
+        assert!(hold.is_held(now + Duration::from_secs(10)));
+        hold.release();
+        assert!(!hold.is_held(now));
+    }
+}
@@ -0,0 +1,252 @@
+use crossterm::event::KeyCode;
+
+/// Something a player can do by pressing a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Help,
+    Settings,
+    Mute,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Help,
+        Action::Settings,
+        Action::Mute,
+        Action::Quit,
+    ];
+
+    /// The name used for the action in profile files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Help => "help",
+            Action::Settings => "settings",
+            Action::Mute => "mute",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            Action::Up => "Move paddle up",
+            Action::Down => "Move paddle down",
+            Action::Left => "Move paddle left",
+            Action::Right => "Move paddle right",
+            Action::Help => "Toggle help",
+            Action::Settings => "Open settings",
+            Action::Mute => "Toggle sounds",
+            Action::Quit => "Quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// Maps keys to actions.
+///
+/// The default keymap binds WASD, arrow and vim keys for movement. Players can override the keys
+/// for any action, and the overrides are saved with their [`Profile`](crate::profile::Profile).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    // indexed in the same order as `Action::ALL`
+    bindings: [Vec<KeyCode>; Action::ALL.len()],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL.map(Self::default_keys),
+        }
+    }
+}
+
+impl Keymap {
+    fn default_keys(action: Action) -> Vec<KeyCode> {
+        match action {
+            Action::Up => vec![KeyCode::Char('w'), KeyCode::Up, KeyCode::Char('k')],
+            Action::Down => vec![KeyCode::Char('s'), KeyCode::Down, KeyCode::Char('j')],
+            Action::Left => vec![KeyCode::Char('a'), KeyCode::Left, KeyCode::Char('h')],
+            Action::Right => vec![KeyCode::Char('d'), KeyCode::Right, KeyCode::Char('l')],
+            Action::Help => vec![KeyCode::Char('?')],
+            Action::Settings => vec![KeyCode::Char('o')],
+            Action::Mute => vec![KeyCode::Char('m')],
+            Action::Quit => vec![KeyCode::Char('q')],
+        }
+    }
+
+    /// The action bound to the given key, if any.
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|&action| self.keys(action).contains(&code))
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        &self.bindings[action as usize]
+    }
+
+    /// Bind the action to the given keys, replacing its existing keys.
+    ///
+    /// The keys are removed from any other action they were bound to. An action that would be
+    /// left without any keys takes over the keys the rebound action had instead, so players can't
+    /// lose the keys for opening the settings or quitting. Returns false, leaving the keymap
+    /// unchanged, if some action would still be left without keys.
+    pub fn bind(&mut self, action: Action, keys: &[KeyCode]) -> bool {
+        if keys.is_empty() {
+            return false;
+        }
+        let mut previous: Vec<_> = self
+            .keys(action)
+            .iter()
+            .filter(|code| !keys.contains(code))
+            .copied()
+            .collect();
+        let mut bindings = self.bindings.clone();
+        bindings[action as usize] = keys.to_vec();
+        for (other, bound) in Action::ALL.into_iter().zip(&mut bindings) {
+            if other == action || !bound.iter().any(|code| keys.contains(code)) {
+                continue;
+            }
+            bound.retain(|code| !keys.contains(code));
+            if bound.is_empty() {
+                // only one action can take over the previous keys
+                *bound = std::mem::take(&mut previous);
+                if bound.is_empty() {
+                    return false;
+                }
+            }
+        }
+        self.bindings = bindings;
+        true
+    }
+
+    /// Restore the default keys for the action, returning false if that would leave another
+    /// action without keys.
+    pub fn reset(&mut self, action: Action) -> bool {
+        self.bind(action, &Self::default_keys(action))
+    }
+
+    /// The actions whose keys differ from the defaults.
+    pub fn overrides(&self) -> impl Iterator<Item = (Action, &[KeyCode])> {
+        Action::ALL
+            .into_iter()
+            .filter(|&action| self.keys(action) != Self::default_keys(action))
+            .map(|action| (action, self.keys(action)))
+    }
+}
+
+/// The name of a key as shown to players and stored in profile files.
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        code => code.to_string(),
+    }
+}
+
+/// Parse a key name produced by [`key_name`].
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "Space" => KeyCode::Char(' '),
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Insert" => KeyCode::Insert,
+        "Delete" => KeyCode::Delete,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => match name.strip_prefix('F').map(str::parse) {
+                    Some(Ok(n @ 1..)) => KeyCode::F(n),
+                    _ => return None,
+                },
+            }
+        }
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_round_trip() {
+        for code in [
+            KeyCode::Char('w'),
+            KeyCode::Char(' '),
+            KeyCode::Char('F'),
+            KeyCode::Up,
+            KeyCode::Esc,
+            KeyCode::PageDown,
+            KeyCode::F(1),
+            KeyCode::F(12),
+        ] {
+            assert_eq!(parse_key(&key_name(code)), Some(code), "{code:?}");
+        }
+        assert_eq!(parse_key("F0"), None);
+        assert_eq!(parse_key("Fx"), None);
+    }
+
+    #[test]
+    fn rebinding_keeps_every_action_bound() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.bind(Action::Up, &[KeyCode::Char('o')]));
+        assert_eq!(keymap.keys(Action::Up), [KeyCode::Char('o')]);
+        // settings takes over the keys up had
+        assert_eq!(
+            keymap.keys(Action::Settings),
+            [KeyCode::Char('w'), KeyCode::Up, KeyCode::Char('k')]
+        );
+
+        // resetting swaps them back
+        assert!(keymap.reset(Action::Up));
+        assert!(keymap.reset(Action::Settings));
+        assert_eq!(keymap, Keymap::default());
+
+        // there are no keys left to give quit
+        let keys = [KeyCode::Char('?'), KeyCode::Char('q')];
+        assert!(!keymap.bind(Action::Help, &keys));
+        assert!(!keymap.bind(Action::Help, &[]));
+        assert_eq!(keymap, Keymap::default());
+    }
+}
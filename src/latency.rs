@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+/// Measures the round trip time to a client's terminal.
+///
+/// The server periodically asks the terminal for its status, which it answers straight away, so
+/// the time until the answer arrives covers the network in both directions as well as the SSH
+/// client and the terminal itself. Measurements are smoothed like TCP's round trip estimate, so a
+/// single slow reply doesn't make the latency jump around.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LatencyProbe {
+    // When the probe waiting for an answer was sent
+    pending: Option<Instant>,
+    // When the last probe was sent
+    last_sent: Option<Instant>,
+    smoothed: Option<Duration>,
+    // Whether the latency is above the warning threshold
+    high: bool,
+}
+
+/// The latency after an answer to a probe arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// The round trip time of this probe.
+    pub round_trip: Duration,
+    /// The latency smoothed over recent probes.
+    pub smoothed: Duration,
+    /// Whether the smoothed latency has just gone above [`LatencyProbe::WARNING_THRESHOLD`].
+    pub became_high: bool,
+}
+
+impl LatencyProbe {
+    /// Latency above this makes the game noticeably sluggish, so players are warned about it.
+    pub const WARNING_THRESHOLD: Duration = Duration::from_millis(150);
+    const INTERVAL: Duration = Duration::from_secs(2);
+    // Probes that haven't been answered by then are assumed lost and sent again
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    // The warning is only given again once the latency has dropped below this
+    const RECOVERY_THRESHOLD: Duration = Duration::from_millis(100);
+
+    /// Whether a probe should be sent now, in which case it is recorded as sent.
+    pub fn send(&mut self, now: Instant) -> bool {
+        let due = match (self.pending, self.last_sent) {
+            (Some(pending), _) => now - pending >= Self::TIMEOUT,
+            (None, Some(last_sent)) => now - last_sent >= Self::INTERVAL,
+            (None, None) => true,
+        };
+        if due {
+            self.pending = Some(now);
+            self.last_sent = Some(now);
+        }
+        due
+    }
+
+    /// Record the answer to the pending probe, if there is one.
+    pub fn answer(&mut self, now: Instant) -> Option<Measurement> {
+        let round_trip = now - self.pending.take()?;
+        let smoothed = match self.smoothed {
+            Some(smoothed) => (smoothed * 7 + round_trip) / 8,
+            None => round_trip,
+        };
+        self.smoothed = Some(smoothed);
+        let was_high = self.high;
+        if smoothed > Self::WARNING_THRESHOLD {
+            self.high = true;
+        } else if smoothed < Self::RECOVERY_THRESHOLD {
+            self.high = false;
+        }
+        Some(Measurement {
+            round_trip,
+            smoothed,
+            became_high: self.high && !was_high,
+        })
+    }
+
+    /// The latency smoothed over recent probes, if any have been answered.
+    pub fn latency(&self) -> Option<Duration> {
+        self.smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(probe: &mut LatencyProbe, start: Instant, millis: u64) -> Measurement {
+        assert!(probe.send(start));
+        probe.answer(start + Duration::from_millis(millis)).unwrap()
+    }
+
+    #[test]
+    fn smoothing_and_warning() {
+        let mut probe = LatencyProbe::default();
+        let mut now = Instant::now();
+        assert_eq!(probe.answer(now), None);
+
+        let first = round_trip(&mut probe, now, 40);
+        assert_eq!(first.smoothed, Duration::from_millis(40));
+        assert!(!first.became_high);
+        assert!(!probe.send(now + Duration::from_secs(1)));
+
+        // a single slow reply is smoothed over
+        now += LatencyProbe::INTERVAL;
+        let spike = round_trip(&mut probe, now, 440);
+        assert_eq!(spike.round_trip, Duration::from_millis(440));
+        assert_eq!(spike.smoothed, Duration::from_millis(90));
+        assert!(!spike.became_high);
+
+        // lasting latency is warned about once
+        let measurements: Vec<_> = (0..10)
+            .map(|_| {
+                now += LatencyProbe::INTERVAL;
+                round_trip(&mut probe, now, 300)
+            })
+            .collect();
+        assert_eq!(measurements.iter().filter(|m| m.became_high).count(), 1);
+        assert!(measurements[9].smoothed > LatencyProbe::WARNING_THRESHOLD);
+        assert_eq!(probe.latency(), Some(measurements[9].smoothed));
+    }
+
+    #[test]
+    fn lost_probe_is_sent_again() {
+        let mut probe = LatencyProbe::default();
+        let now = Instant::now();
+        assert!(probe.send(now));
+        assert!(!probe.send(now + LatencyProbe::INTERVAL));
+        assert!(probe.send(now + LatencyProbe::TIMEOUT));
+    }
+}
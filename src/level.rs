@@ -0,0 +1,392 @@
+use std::{fs, time::Duration};
+
+use color_eyre::Result;
+use ratatui::layout::Rect;
+use tracing::{info, warn};
+
+use crate::{
+    court::{Fill, Marks},
+    physics::{Bounds, Point, Side, Velocity},
+    profile,
+};
+
+/// A rectangle in the court that the ball bounces off.
+///
+/// Moving obstacles travel at a constant velocity and bounce off the sides of the court.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub bounds: Bounds,
+    pub vel: Velocity,
+}
+
+impl Obstacle {
+    fn update(&mut self, duration: Duration) {
+        let dt = duration.as_secs_f32();
+        let bounds = &mut self.bounds;
+        bounds.x += self.vel.x * dt;
+        bounds.y += self.vel.y * dt;
+        if bounds.x < 0.0 || bounds.right() > 1.0 {
+            bounds.x = bounds.x.clamp(0.0, 1.0 - bounds.width);
+            self.vel.x = -self.vel.x;
+        }
+        if bounds.y < 0.0 || bounds.bottom() > 1.0 {
+            bounds.y = bounds.y.clamp(0.0, 1.0 - bounds.height);
+            self.vel.y = -self.vel.y;
+        }
+    }
+}
+
+/// Moves the ball from one point in the court to another, keeping its velocity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Portal {
+    pub entry: Point,
+    pub exit: Point,
+}
+
+impl Portal {
+    /// How close the ball needs to get to the entry to go through.
+    pub const RADIUS: f32 = 0.03;
+
+    pub fn is_at_entry(&self, pos: Point) -> bool {
+        (pos.x - self.entry.x).hypot(pos.y - self.entry.y) < Self::RADIUS
+    }
+}
+
+/// The layout of a court: obstacles, portals and how wide the goals are.
+///
+/// The default level is an open court.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub obstacles: Vec<Obstacle>,
+    pub portals: Vec<Portal>,
+    /// The width of the goal in the middle of each side guarded by a paddle, as a fraction of the
+    /// side. The rest of the side is a wall.
+    pub goal_width: f32,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self::open()
+    }
+}
+
+impl Level {
+    /// The classic court, with nothing in the way.
+    pub fn open() -> Self {
+        Self {
+            name: "open".to_string(),
+            obstacles: Vec::new(),
+            portals: Vec::new(),
+            goal_width: 1.0,
+        }
+    }
+
+    /// Two pillars on opposite sides of the net.
+    pub fn pillars() -> Self {
+        Self::parse(
+            "pillars",
+            "obstacle = 0.3 0.2 0.04 0.2\nobstacle = 0.66 0.6 0.04 0.2\n",
+        )
+    }
+
+    /// A block sliding up and down the middle of the court.
+    pub fn elevator() -> Self {
+        Self::parse("elevator", "obstacle = 0.48 0.05 0.04 0.2 0 0.25\n")
+    }
+
+    /// Portals near the top and bottom of the net that swap the ball between them.
+    pub fn portals() -> Self {
+        Self::parse(
+            "portals",
+            "portal = 0.5 0.15 0.5 0.85\nportal = 0.5 0.85 0.5 0.15\n",
+        )
+    }
+
+    /// Goals half the height of the court, with walls either side.
+    pub fn narrow() -> Self {
+        Self::parse("narrow", "goal = 0.5\n")
+    }
+
+    /// Parse a level file.
+    ///
+    /// Level files use the same `key = value` lines as profiles and themes, with positions and
+    /// sizes as fractions of the court:
+    ///
+    /// - `obstacle = x y width height` places an obstacle, and `obstacle = x y width height dx dy`
+    ///   places one moving at the given velocity.
+    /// - `portal = x y to_x to_y` places a portal that moves the ball from one point to the other.
+    /// - `goal = width` narrows the goals.
+    ///
+    /// Invalid lines, including ones with anything outside the court, are logged and skipped.
+    fn parse(name: &str, contents: &str) -> Self {
+        let mut level = Self {
+            name: name.to_string(),
+            ..Self::open()
+        };
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let Ok(numbers) = value
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<f32>, _>>()
+            else {
+                warn!("Invalid line in level {name}: {line}");
+                continue;
+            };
+            // also false for NaN
+            let in_court = |n: &f32| (0.0..=1.0).contains(n);
+            let fits = |x: f32, y: f32, width: f32, height: f32| {
+                [x, y, x + width, y + height].iter().all(in_court) && width > 0.0 && height > 0.0
+            };
+            match (key, numbers.as_slice()) {
+                ("obstacle", &[x, y, width, height]) if fits(x, y, width, height) => {
+                    level.obstacles.push(Obstacle {
+                        bounds: Bounds::new(x, y, width, height),
+                        vel: Velocity::default(),
+                    })
+                }
+                ("obstacle", &[x, y, width, height, dx, dy])
+                    if fits(x, y, width, height) && dx.is_finite() && dy.is_finite() =>
+                {
+                    level.obstacles.push(Obstacle {
+                        bounds: Bounds::new(x, y, width, height),
+                        vel: Velocity::new(dx, dy),
+                    })
+                }
+                ("portal", points @ &[x, y, to_x, to_y]) if points.iter().all(in_court) => {
+                    level.portals.push(Portal {
+                        entry: Point::new(x, y),
+                        exit: Point::new(to_x, to_y),
+                    })
+                }
+                ("goal", &[width]) if in_court(&width) => level.goal_width = width,
+                _ => warn!("Invalid line in level {name}: {line}"),
+            }
+        }
+        level
+    }
+
+    /// Move the moving obstacles.
+    pub fn update(&mut self, duration: Duration) {
+        for obstacle in &mut self.obstacles {
+            obstacle.update(duration);
+        }
+    }
+
+    /// Whether the given position along a guarded side is in the goal rather than the wall.
+    pub fn is_in_goal(&self, offset: f32) -> bool {
+        (offset - 0.5).abs() <= self.goal_width / 2.0
+    }
+
+    /// The level reflected across the vertical center line of the court.
+    pub fn mirrored(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            obstacles: self
+                .obstacles
+                .iter()
+                .map(|obstacle| Obstacle {
+                    bounds: obstacle.bounds.mirrored(),
+                    vel: Velocity::new(-obstacle.vel.x, obstacle.vel.y),
+                })
+                .collect(),
+            portals: self
+                .portals
+                .iter()
+                .map(|portal| Portal {
+                    entry: portal.entry.mirrored(),
+                    exit: portal.exit.mirrored(),
+                })
+                .collect(),
+            goal_width: self.goal_width,
+        }
+    }
+
+    /// The obstacles, portals and the walls either side of the goals on the given sides, drawn in
+    /// the given area.
+    ///
+    /// Returns the obstacles and walls first, then the portals, so they can be drawn in different
+    /// colors.
+    pub fn marks(&self, area: Rect, guarded: &[Side]) -> (Marks, Marks) {
+        let mut solid: Vec<Rect> = self
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.bounds.to_screen(area))
+            .collect();
+        if self.goal_width < 1.0 {
+            let wall = (1.0 - self.goal_width) / 2.0;
+            // thin enough to cover a single row or column
+            let thickness = 0.001;
+            for side in guarded {
+                let walls = match side {
+                    Side::Left | Side::Right => {
+                        let x = if *side == Side::Left {
+                            0.0
+                        } else {
+                            1.0 - thickness
+                        };
+                        [
+                            Bounds::new(x, 0.0, thickness, wall),
+                            Bounds::new(x, 1.0 - wall, thickness, wall),
+                        ]
+                    }
+                    Side::Top | Side::Bottom => {
+                        let y = if *side == Side::Top {
+                            0.0
+                        } else {
+                            1.0 - thickness
+                        };
+                        [
+                            Bounds::new(0.0, y, wall, thickness),
+                            Bounds::new(1.0 - wall, y, wall, thickness),
+                        ]
+                    }
+                };
+                solid.extend(walls.map(|bounds| bounds.to_screen(area)));
+            }
+        }
+        let portals = self
+            .portals
+            .iter()
+            .flat_map(|portal| [portal.entry, portal.exit])
+            .map(|pos| {
+                let pos = pos.to_screen(area);
+                Rect::new(pos.x, pos.y, 1, 1)
+            })
+            .collect();
+        (
+            Marks {
+                fill: Fill::Block,
+                cells: solid,
+            },
+            Marks {
+                fill: Fill::Dot,
+                cells: portals,
+            },
+        )
+    }
+}
+
+/// The levels players can choose from: the built-in levels followed by any loaded from files.
+#[derive(Debug, Clone)]
+pub struct Levels {
+    levels: Vec<Level>,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            levels: vec![
+                Level::open(),
+                Level::pillars(),
+                Level::elevator(),
+                Level::portals(),
+                Level::narrow(),
+            ],
+        }
+    }
+}
+
+impl Levels {
+    /// Load the built-in levels and any `*.level` files in the `levels` config directory.
+    ///
+    /// A file with the same name as a built-in level replaces it. Unreadable files are logged and
+    /// skipped.
+    pub fn load() -> Result<Self> {
+        let mut levels = Self::default();
+        let dir = profile::config_dir()?.join("levels");
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(levels);
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let Some(name) = path
+                .file_stem()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "level"))
+                .and_then(|name| name.to_str())
+            else {
+                continue;
+            };
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    info!("Loading level from {}", path.display());
+                    levels.insert(Level::parse(name, &contents));
+                }
+                Err(err) => warn!("Failed to read level from {}: {err}", path.display()),
+            }
+        }
+        Ok(levels)
+    }
+
+    fn insert(&mut self, level: Level) {
+        match self.levels.iter_mut().find(|l| l.name == level.name) {
+            Some(existing) => *existing = level,
+            None => self.levels.push(level),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Level> {
+        self.levels.iter().find(|level| level.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_level_file() {
+        let contents = "\
+obstacle = 0.1 0.2 0.3 0.4
+obstacle = 0.5 0.5 0.1 0.1 0 0.2
+portal = 0.2 0.2 0.8 0.8
+goal = 0.6
+obstacle = nope
+obstacle = 0.1 0.2 0.3 0.4 junk
+portal = 0.2 0.2 0.8 0.8 0.5
+";
+        let level = Level::parse("maze", contents);
+        assert_eq!(level.name, "maze");
+        assert_eq!(level.obstacles.len(), 2);
+        assert_eq!(level.portals.len(), 1);
+        assert_eq!(level.obstacles[1].vel.y, 0.2);
+        assert_eq!(level.portals[0].exit.x, 0.8);
+        assert_eq!(level.goal_width, 0.6);
+    }
+
+    #[test]
+    fn reject_values_outside_the_court() {
+        let contents = "\
+obstacle = 0.1 0.2 -0.3 0.4
+obstacle = 0.9 0.2 0.3 0.4
+obstacle = NaN 0.2 0.3 0.4
+obstacle = 0.1 0.2 0.3 0.4 inf 0
+portal = 0.2 0.2 5 0.8
+portal = 0.2 0.2 0.8 NaN
+goal = NaN
+obstacle = 0.7 0.6 0.3 0.4
+portal = 0 1 1 0
+";
+        let level = Level::parse("broken", contents);
+        assert_eq!(level.obstacles.len(), 1);
+        assert_eq!(level.portals.len(), 1);
+        assert_eq!(level.goal_width, Level::open().goal_width);
+        // the built-in levels are all within the court
+        assert_eq!(Level::pillars().obstacles.len(), 2);
+        assert_eq!(Level::elevator().obstacles.len(), 1);
+        assert_eq!(Level::portals().portals.len(), 2);
+    }
+
+    #[test]
+    fn narrow_goals() {
+        let level = Level::narrow();
+        assert!(level.is_in_goal(0.5));
+        assert!(level.is_in_goal(0.75));
+        assert!(!level.is_in_goal(0.8));
+        assert!(!level.is_in_goal(0.1));
+        assert!(Level::open().is_in_goal(0.0));
+    }
+}
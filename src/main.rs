@@ -4,10 +4,31 @@ use tracing_subscriber::EnvFilter;
 
 mod backend;
 mod ball;
+mod capabilities;
+mod client;
+mod court;
+mod effects;
+mod event;
 mod game;
+mod graphics;
+mod help;
+mod input;
+mod keymap;
+mod latency;
+mod level;
+mod mode;
 mod paddle;
 mod physics;
+mod power_up;
+mod profile;
+mod room;
 mod server;
+mod settings;
+mod sound;
+mod subcell;
+mod theme;
+mod tournament;
+mod viewer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -0,0 +1,328 @@
+use std::fmt;
+
+use crate::{
+    ball::Physics,
+    game::Game,
+    level::{Level, Levels},
+    paddle::Handicap,
+    physics::Side,
+};
+
+/// The kind of match being played.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Two players on the left and right, playing to [`Game::WINNING_SCORE`] points.
+    ///
+    /// [`Game::WINNING_SCORE`]: crate::game::Game::WINNING_SCORE
+    #[default]
+    Classic,
+    /// A player on each side of the court. Letting the ball past loses a life, and the last
+    /// player with lives left wins.
+    FourPlayer,
+    /// Two teams of two on the left and right, playing to [`Game::WINNING_SCORE`] points. Each
+    /// player guards one half of their team's side.
+    ///
+    /// [`Game::WINNING_SCORE`]: crate::game::Game::WINNING_SCORE
+    Doubles,
+    /// A single player on the left, returning the ball off the walls for as long as they can. The
+    /// ball speeds up with each return.
+    Practice,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 4] = [
+        Mode::Classic,
+        Mode::FourPlayer,
+        Mode::Doubles,
+        Mode::Practice,
+    ];
+
+    /// The name used to choose the mode when starting a match.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Classic => "classic",
+            Mode::FourPlayer => "four-player",
+            Mode::Doubles => "doubles",
+            Mode::Practice => "practice",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// The side guarded by the paddle in each player slot.
+    ///
+    /// Slots are filled in order, so teams are filled evenly as players join.
+    pub const fn sides(self) -> &'static [Side] {
+        match self {
+            Mode::Classic => &[Side::Left, Side::Right],
+            Mode::FourPlayer => &[Side::Left, Side::Right, Side::Top, Side::Bottom],
+            Mode::Doubles => &[Side::Left, Side::Right, Side::Left, Side::Right],
+            Mode::Practice => &[Side::Left],
+        }
+    }
+
+    /// The number of teams, which each have a score (or lives) of their own.
+    pub const fn teams(self) -> usize {
+        match self {
+            Mode::Classic | Mode::Doubles => 2,
+            Mode::FourPlayer => 4,
+            Mode::Practice => 1,
+        }
+    }
+
+    /// The team the player in the given slot plays for. Team 0 is on the left in modes with two
+    /// teams.
+    pub const fn team(self, slot: usize) -> usize {
+        slot % self.teams()
+    }
+
+    /// The part of their side the paddle in the given slot can move along, from the top or left.
+    pub const fn lane(self, slot: usize) -> (f32, f32) {
+        match self {
+            Mode::Doubles if slot < 2 => (0.0, 0.5),
+            Mode::Doubles => (0.5, 1.0),
+            Mode::Classic | Mode::FourPlayer | Mode::Practice => (0.0, 1.0),
+        }
+    }
+
+    /// Where the player in the given slot plays, e.g. "the left" or "the top half of the left".
+    pub fn position_name(self, slot: usize) -> String {
+        let side = self.sides()[slot].name();
+        match self.lane(slot) {
+            (0.0, 1.0) => format!("the {side}"),
+            (0.0, _) => format!("the top half of the {side}"),
+            _ => format!("the bottom half of the {side}"),
+        }
+    }
+
+    /// How many times each team can let the ball past, for modes played with lives rather than
+    /// points.
+    pub const fn lives(self) -> Option<u32> {
+        match self {
+            Mode::FourPlayer => Some(5),
+            Mode::Classic | Mode::Doubles | Mode::Practice => None,
+        }
+    }
+}
+
+/// The options chosen for a match by the player who starts it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchConfig {
+    pub mode: Mode,
+    pub level: Level,
+    pub physics: Physics,
+    /// Whether power-ups appear on the court.
+    pub power_ups: bool,
+    /// How many balls are served at once.
+    pub balls: usize,
+    /// The handicap for the paddle in each player slot.
+    pub handicaps: [Handicap; Game::MAX_PLAYERS],
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            level: Level::default(),
+            physics: Physics::default(),
+            power_ups: false,
+            balls: 1,
+            handicaps: [Handicap::default(); Game::MAX_PLAYERS],
+        }
+    }
+}
+
+impl MatchConfig {
+    /// The most balls that can be served at once.
+    pub const MAX_BALLS: usize = 8;
+
+    /// Parse the options given as the SSH command, such as `ssh -t pong.example four-player`.
+    ///
+    /// Options are separated by whitespace, and are either the name of a [`Mode`], `level=NAME` to
+    /// choose one of the given levels, `physics=NAME` to choose the [`Physics`], `balls=N` to serve
+    /// several balls at once, `power-ups`, or
+    /// a handicap such as `p2.speed=0.8` (see [`MatchConfig::set_handicap`]). An empty command
+    /// chooses the defaults.
+    pub fn parse(command: &str, levels: &Levels) -> Result<Self, ParseError> {
+        let mut config = Self::default();
+        for option in command.split_whitespace() {
+            if let Some(name) = option.strip_prefix("level=") {
+                let level = levels
+                    .get(name)
+                    .ok_or_else(|| ParseError::UnknownLevel(name.to_string()))?;
+                config.level = level.clone();
+                continue;
+            }
+            if let Some(name) = option.strip_prefix("physics=") {
+                config.physics = Physics::from_name(name)
+                    .ok_or_else(|| ParseError::UnknownOption(option.to_string()))?;
+                continue;
+            }
+            if let Some(count) = option.strip_prefix("balls=") {
+                config.balls = count
+                    .parse()
+                    .ok()
+                    .filter(|count| (1..=Self::MAX_BALLS).contains(count))
+                    .ok_or_else(|| ParseError::InvalidBalls(count.to_string()))?;
+                continue;
+            }
+            if option == "power-ups" {
+                config.power_ups = true;
+                continue;
+            }
+            if let Some((name, value)) = option
+                .split_once('=')
+                .filter(|(name, _)| name.starts_with('p') && name.contains('.'))
+            {
+                config
+                    .set_handicap(name, value)
+                    .ok_or_else(|| ParseError::InvalidHandicap(option.to_string()))?;
+                continue;
+            }
+            match Mode::from_name(option) {
+                Some(mode) => config.mode = mode,
+                None => return Err(ParseError::UnknownOption(option.to_string())),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Set one value of a player's handicap, given as `pN.length`, `pN.speed` or
+    /// `pN.acceleration` for the player in slot `N` (counting from 1), and a multiple of the usual
+    /// value within [`Handicap::RANGE`].
+    ///
+    /// Returns `None` if the name or value is invalid.
+    fn set_handicap(&mut self, name: &str, value: &str) -> Option<()> {
+        let (player, setting) = name.strip_prefix('p')?.split_once('.')?;
+        let slot = player.parse::<usize>().ok()?.checked_sub(1)?;
+        let handicap = self.handicaps.get_mut(slot)?;
+        let value = value
+            .parse()
+            .ok()
+            .filter(|value| Handicap::RANGE.contains(value))?;
+        match setting {
+            "length" => handicap.length = value,
+            "speed" => handicap.speed = value,
+            "acceleration" => handicap.acceleration = value,
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// An error in the options given for a match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownOption(String),
+    UnknownLevel(String),
+    InvalidBalls(String),
+    InvalidHandicap(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownOption(option) => write!(f, "Unknown match option: {option}"),
+            ParseError::UnknownLevel(name) => write!(f, "Unknown level: {name}"),
+            ParseError::InvalidBalls(count) => write!(
+                f,
+                "Invalid number of balls: {count} (1 to {})",
+                MatchConfig::MAX_BALLS
+            ),
+            ParseError::InvalidHandicap(option) => write!(
+                f,
+                "Invalid handicap: {option} (pN.length, pN.speed or pN.acceleration, {} to {})",
+                Handicap::RANGE.start(),
+                Handicap::RANGE.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_options() {
+        let levels = Levels::default();
+        assert_eq!(MatchConfig::parse("", &levels), Ok(MatchConfig::default()));
+        assert_eq!(
+            MatchConfig::parse(" four-player ", &levels),
+            Ok(MatchConfig {
+                mode: Mode::FourPlayer,
+                ..MatchConfig::default()
+            })
+        );
+        assert_eq!(
+            MatchConfig::parse("four-player doubles", &levels).map(|config| config.mode),
+            Ok(Mode::Doubles)
+        );
+        assert_eq!(
+            MatchConfig::parse("five-player", &levels),
+            Err(ParseError::UnknownOption("five-player".to_string()))
+        );
+        assert_eq!(
+            MatchConfig::parse("level=pillars", &levels).map(|config| config.level.name),
+            Ok("pillars".to_string())
+        );
+        assert_eq!(
+            MatchConfig::parse("power-ups doubles", &levels).map(|config| config.power_ups),
+            Ok(true)
+        );
+        assert_eq!(
+            MatchConfig::parse("balls=3", &levels).map(|config| config.balls),
+            Ok(3)
+        );
+        assert_eq!(
+            MatchConfig::parse("balls=0", &levels),
+            Err(ParseError::InvalidBalls("0".to_string()))
+        );
+        assert_eq!(
+            MatchConfig::parse("physics=modern", &levels).map(|config| config.physics),
+            Ok(Physics::Modern)
+        );
+        assert_eq!(
+            MatchConfig::parse("physics=quantum", &levels),
+            Err(ParseError::UnknownOption("physics=quantum".to_string()))
+        );
+        let config = MatchConfig::parse("p2.speed=0.5 p2.length=2", &levels).unwrap();
+        assert_eq!(config.handicaps[0], Handicap::default());
+        assert_eq!(
+            (config.handicaps[1].speed, config.handicaps[1].length),
+            (0.5, 2.0)
+        );
+        assert_eq!(
+            MatchConfig::parse("p5.speed=0.5", &levels),
+            Err(ParseError::InvalidHandicap("p5.speed=0.5".to_string()))
+        );
+        assert_eq!(
+            MatchConfig::parse("p1.speed=10", &levels),
+            Err(ParseError::InvalidHandicap("p1.speed=10".to_string()))
+        );
+        assert_eq!(
+            MatchConfig::parse("level=moon", &levels),
+            Err(ParseError::UnknownLevel("moon".to_string()))
+        );
+    }
+
+    #[test]
+    fn doubles_teams_and_lanes() {
+        let mode = Mode::Doubles;
+        let teams: Vec<_> = (0..4).map(|slot| mode.team(slot)).collect();
+        assert_eq!(teams, [0, 1, 0, 1]);
+        // each team's players share a side, one in each half
+        for slot in 0..2 {
+            assert_eq!(mode.sides()[slot], mode.sides()[slot + 2]);
+            assert_eq!(mode.lane(slot), (0.0, 0.5));
+            assert_eq!(mode.lane(slot + 2), (0.5, 1.0));
+        }
+        assert_eq!(mode.position_name(2), "the bottom half of the left");
+        assert_eq!(Mode::Classic.lane(1), (0.0, 1.0));
+        assert_eq!(Mode::FourPlayer.team(3), 3);
+    }
+}
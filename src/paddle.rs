@@ -1,58 +1,434 @@
-use crate::physics::Point;
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    capabilities::GlyphSupport,
+    input::KeyHold,
+    physics::{Orientation, Point, Side},
+};
 use ratatui::prelude::*;
 
+/// The direction a paddle can be moved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// How a paddle's length and movement differ from the usual, as multiples of the usual values.
+///
+/// Handicaps let players of different skill play a closer match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handicap {
+    pub length: f32,
+    pub speed: f32,
+    pub acceleration: f32,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            length: 1.0,
+            speed: 1.0,
+            acceleration: 1.0,
+        }
+    }
+}
+
+impl Handicap {
+    /// The multiples each value can be set to.
+    pub const RANGE: RangeInclusive<f32> = 0.25..=4.0;
+}
+
 /// Represents a paddle in the game.
 ///
-/// The x coordinate of the paddle is fixed, so it only moves up and down.
-#[derive(Debug, Default)]
+/// A paddle guards one side of the court and only moves along it: up and down on the left and
+/// right, or left and right on the top and bottom. The paddle speeds up to its top speed while
+/// one of its movement keys is held, or heads towards the target set by the mouse.
+#[derive(Debug, Clone)]
 pub struct Paddle {
     pub pos: Point,
+    pub side: Side,
+    /// The velocity along the side in fractions of the court per second, positive towards the
+    /// bottom or right.
+    pub vel: f32,
+    /// The part of the side the paddle can move along, from the top or left.
+    pub lane: (f32, f32),
+    /// The length of the paddle along its side, as a fraction of the side's length.
+    pub length: f32,
+    /// The top speed of the paddle in fractions of the court per second.
+    pub speed: f32,
+    /// How quickly the paddle speeds up and slows down, in fractions of the court per second
+    /// squared.
+    pub acceleration: f32,
+    // Whether the movement keys and mouse are swapped, such as while a power-up is active
+    reversed: bool,
+    // Held keys moving the paddle towards the top or left, and towards the bottom or right
+    back: KeyHold,
+    forward: KeyHold,
+    target: Option<f32>,
+}
+
+impl Default for Paddle {
+    fn default() -> Self {
+        Self::new(Side::Left)
+    }
 }
 
 impl Paddle {
     // const WIDTH: f32 = 0.01;
-    pub const HEIGHT: f32 = 0.15;
-    const MOVE_DELTA: f32 = 0.025;
+    /// The usual length of a paddle along its side, as a fraction of the side's length.
+    pub const LENGTH: f32 = 0.15;
+    /// The usual top speed of a paddle.
+    pub const SPEED: f32 = 0.9;
+    /// The usual acceleration of a paddle, reaching top speed in a few frames.
+    pub const ACCELERATION: f32 = 18.0;
 
-    pub const fn new(x: f32, y: f32) -> Self {
+    /// Create a paddle in the middle of the given side.
+    pub const fn new(side: Side) -> Self {
         Self {
-            pos: Point { x, y },
+            pos: side.center(),
+            side,
+            lane: (0.0, 1.0),
+            length: Self::LENGTH,
+            speed: Self::SPEED,
+            acceleration: Self::ACCELERATION,
+            reversed: false,
+            vel: 0.0,
+            back: KeyHold::Released,
+            forward: KeyHold::Released,
+            target: None,
+        }
+    }
+
+    /// Keep the paddle within part of its side, starting in the middle of it.
+    pub fn with_lane(mut self, start: f32, end: f32) -> Self {
+        self.lane = (start, end);
+        *self.orientation().along_mut(&mut self.pos) = (start + end) / 2.0;
+        self
+    }
+
+    /// Scale the paddle's length, speed and acceleration by the given handicap.
+    pub fn with_handicap(mut self, handicap: Handicap) -> Self {
+        self.length = Self::LENGTH * handicap.length;
+        self.speed = Self::SPEED * handicap.speed;
+        self.acceleration = Self::ACCELERATION * handicap.acceleration;
+        self
+    }
+
+    /// Swap the movement keys and mouse direction, or swap them back.
+    ///
+    /// Held keys are released, as they would otherwise be released in the other direction.
+    pub fn set_reversed(&mut self, reversed: bool) {
+        if self.reversed != reversed {
+            self.reversed = reversed;
+            self.back.release();
+            self.forward.release();
+            self.target = None;
+        }
+    }
+
+    pub const fn orientation(&self) -> Orientation {
+        self.side.orientation()
+    }
+
+    /// The position of the center of the paddle along its side.
+    pub const fn offset(&self) -> f32 {
+        self.orientation().along(self.pos)
+    }
+
+    /// The held key for the given direction, or `None` if the paddle can't move that way.
+    fn hold_mut(&mut self, direction: Direction) -> Option<&mut KeyHold> {
+        let back = match (self.orientation(), direction) {
+            (Orientation::Vertical, Direction::Up) | (Orientation::Horizontal, Direction::Left) => {
+                true
+            }
+            (Orientation::Vertical, Direction::Down)
+            | (Orientation::Horizontal, Direction::Right) => false,
+            _ => return None,
+        };
+        if back != self.reversed {
+            Some(&mut self.back)
+        } else {
+            Some(&mut self.forward)
+        }
+    }
+
+    /// Start (or keep) moving the paddle in the given direction.
+    ///
+    /// `release_expected` should be true when the client's terminal reports key releases.
+    /// Directions across the paddle's side are ignored.
+    pub fn press(&mut self, direction: Direction, release_expected: bool) {
+        let now = Instant::now();
+        if let Some(hold) = self.hold_mut(direction) {
+            hold.press(now, release_expected);
+            self.target = None;
+        }
+    }
+
+    /// Stop moving the paddle in the given direction.
+    pub fn release(&mut self, direction: Direction) {
+        if let Some(hold) = self.hold_mut(direction) {
+            hold.release();
+        }
+    }
+
+    /// Move the paddle towards the given position along its side.
+    ///
+    /// The paddle travels at its normal speed rather than jumping, so mouse players have no
+    /// advantage over keyboard players. Pressing a key cancels the target.
+    pub fn move_to(&mut self, offset: f32) {
+        let offset = if self.reversed { 1.0 - offset } else { offset };
+        self.target = Some(self.clamp(offset));
+    }
+
+    /// The nearest position to the given one where the paddle stays within its lane.
+    fn clamp(&self, offset: f32) -> f32 {
+        let (start, end) = self.lane;
+        // a paddle longer than its lane stays in the middle of it
+        let half = (self.length / 2.0).min((end - start) / 2.0);
+        offset.clamp(start + half, end - half)
+    }
+
+    /// Move the paddle according to the held keys or the mouse target.
+    ///
+    /// Like the ball, the paddle moves by its velocity scaled by the time since the last update,
+    /// so it moves at the same speed regardless of the refresh rate or key repeat rate. The
+    /// velocity changes by at most the paddle's acceleration.
+    pub fn update(&mut self, duration: Duration) {
+        let now = Instant::now();
+        let dt = duration.as_secs_f32();
+        let offset = self.offset();
+        let held = (self.back.is_held(now), self.forward.is_held(now));
+        let wanted = match held {
+            (true, false) => -self.speed,
+            (false, true) => self.speed,
+            (true, true) => 0.0,
+            (false, false) => match self.target {
+                Some(target) if dt > 0.0 => {
+                    // slow down in time to stop at the target rather than overshooting it
+                    let distance = target - offset;
+                    let stopping = (2.0 * self.acceleration * distance.abs()).sqrt();
+                    distance.signum() * self.speed.min(stopping).min(distance.abs() / dt)
+                }
+                _ => 0.0,
+            },
+        };
+        let change = self.acceleration * dt;
+        self.vel += (wanted - self.vel).clamp(-change, change);
+        let mut moved = offset + self.vel * dt;
+        if let (false, false, Some(target)) = (held.0, held.1, self.target) {
+            if (target - offset) * (target - moved) <= 0.0 {
+                // arrived, rather than carrying on past the target while slowing down
+                moved = target;
+                self.vel = 0.0;
+            }
+        }
+        let clamped = self.clamp(moved);
+        if clamped != moved {
+            // stopped by the end of the lane
+            self.vel = 0.0;
         }
+        *self.orientation().along_mut(&mut self.pos) = clamped;
+    }
+
+    /// Whether the paddle covers the given position along its side.
+    pub fn covers(&self, offset: f32) -> bool {
+        (offset - self.offset()).abs() < self.length / 2.0
+    }
+
+    /// The start and end edges of the paddle on the screen, in fractional rows from the top of
+    /// the area or columns from the left.
+    fn screen_edges(&self, area: Rect) -> (f32, f32) {
+        let length = match self.orientation() {
+            Orientation::Vertical => area.height,
+            Orientation::Horizontal => area.width,
+        };
+        let start = (self.offset() - self.length / 2.0) * length as f32;
+        let end = (self.offset() + self.length / 2.0) * length as f32;
+        (start, end)
     }
 
-    /// Move the paddle up by a small amount
-    pub fn move_up(&mut self) {
-        self.pos.y = f32::max(self.pos.y - Self::MOVE_DELTA, Self::HEIGHT / 2.0);
+    fn screen_x(&self, area: Rect) -> u16 {
+        (self.pos.x * (area.width.saturating_sub(1)) as f32) as u16 + area.x
     }
 
-    /// Move the paddle down by a small amount
-    pub fn move_down(&mut self) {
-        self.pos.y = f32::min(self.pos.y + Self::MOVE_DELTA, 1.0 - Self::HEIGHT / 2.0);
+    fn screen_y(&self, area: Rect) -> u16 {
+        (self.pos.y * (area.height.saturating_sub(1)) as f32) as u16 + area.y
+    }
+
+    /// The cells the paddle covers when drawn in the given area.
+    pub fn screen_area(&self, area: Rect) -> Rect {
+        let (start, end) = self.screen_edges(area);
+        let rect = match self.orientation() {
+            Orientation::Vertical => {
+                let top = start as u16 + area.y;
+                let bottom = end as u16 + area.y;
+                Rect::new(self.screen_x(area), top, 1, bottom - top + 1)
+            }
+            Orientation::Horizontal => {
+                let left = start as u16 + area.x;
+                let right = end as u16 + area.x;
+                Rect::new(left, self.screen_y(area), right - left + 1, 1)
+            }
+        };
+        rect.intersection(area)
+    }
+}
+
+impl Paddle {
+    /// A widget that draws the paddle using the glyphs the terminal supports.
+    pub fn widget(&self, glyphs: GlyphSupport) -> PaddleWidget<'_> {
+        PaddleWidget {
+            paddle: self,
+            glyphs,
+            style: Style::default(),
+        }
+    }
+}
+
+/// Draws a [`Paddle`] with a particular level of glyph support. See [`Paddle::widget`].
+pub struct PaddleWidget<'a> {
+    paddle: &'a Paddle,
+    glyphs: GlyphSupport,
+    style: Style,
+}
+
+impl PaddleWidget<'_> {
+    pub fn style(mut self, style: impl Into<Style>) -> Self {
+        self.style = style.into();
+        self
     }
 }
 
 impl Widget for &Paddle {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.widget(GlyphSupport::Legacy).render(area, buf);
+    }
+}
+
+impl Widget for PaddleWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         // use block characters that represent 1/8th of a cell to draw the paddles
         const TOP_BARS: [&str; 9] = ["█", "▇", "▆", "▅", "▄", "▃", "▂", "▁", " "];
         const BOTTOM_BARS: [&str; 9] = [" ", "▔", "🮂", "🮃", "▀", "🮄", "🮅", "🮆", "█"];
-        let x = (self.pos.x * (area.width.saturating_sub(1)) as f32) as u16 + area.x;
-        let top = (self.pos.y - Paddle::HEIGHT / 2.0) * area.height as f32;
-        let bottom = (self.pos.y + Paddle::HEIGHT / 2.0) * area.height as f32;
+        // without legacy computing glyphs, round to the nearest upper block element
+        const BOTTOM_BARS_BLOCKS: [&str; 9] = [" ", "▔", "▔", "▀", "▀", "▀", "█", "█", "█"];
+        let paddle = self.paddle;
+        if paddle.orientation() == Orientation::Horizontal {
+            return self.render_horizontal(area, buf);
+        }
+        let x = paddle.screen_x(area);
+        if self.glyphs == GlyphSupport::Ascii {
+            for y in paddle.screen_area(area).rows() {
+                buf.set_string(x, y.y, "#", self.style);
+            }
+            return;
+        }
+        let bottom_bars = match self.glyphs {
+            GlyphSupport::Legacy => BOTTOM_BARS,
+            _ => BOTTOM_BARS_BLOCKS,
+        };
+        let (top, bottom) = paddle.screen_edges(area);
         // draw the top character of the paddle by taking the fractional part of the top position
         let index = (top.fract() * 8.0).round() as usize;
         let top_char = TOP_BARS[index];
         let top = top as u16 + area.y;
-        buf.set_string(x, top, top_char, Style::default());
+        buf.set_string(x, top, top_char, self.style);
         // draw the bottom character of the paddle by taking the fractional part of the bottom position
         let index = (bottom.fract() * 8.0).round() as usize;
-        let bottom_char = BOTTOM_BARS[index];
+        let bottom_char = bottom_bars[index];
         let bottom = bottom as u16 + area.y;
-        buf.set_string(x, bottom, bottom_char, Style::default());
+        buf.set_string(x, bottom, bottom_char, self.style);
 
         // fill in the middle of the paddle with block characters
         for y in top + 1..bottom {
-            buf.set_string(x, y, "█", Style::default());
+            buf.set_string(x, y, "█", self.style);
+        }
+    }
+}
+
+impl PaddleWidget<'_> {
+    /// Draw a paddle on the top or bottom of the court in half cell rows, so it is about as thick
+    /// as the paddles on the sides.
+    ///
+    /// The ends are drawn in half cells using quadrant block elements.
+    fn render_horizontal(self, area: Rect, buf: &mut Buffer) {
+        let paddle = self.paddle;
+        let y = paddle.screen_y(area);
+        let cells = paddle.screen_area(area);
+        if self.glyphs == GlyphSupport::Ascii {
+            let symbol = "=".repeat(usize::from(cells.width));
+            buf.set_string(cells.x, y, symbol, self.style);
+            return;
+        }
+        // the half of the cell nearest the court's edge
+        let (full, left_half, right_half) = match paddle.side {
+            Side::Top => ("▀", "▘", "▝"),
+            _ => ("▄", "▖", "▗"),
+        };
+        let (start, end) = paddle.screen_edges(area);
+        for x in cells.columns() {
+            let column = f32::from(x.x - area.x);
+            let symbol = if column + 0.5 < start {
+                right_half
+            } else if column + 0.5 > end {
+                left_half
+            } else {
+                full
+            };
+            buf.set_string(x.x, y, symbol, self.style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    fn run(paddle: &mut Paddle, frames: usize) {
+        for _ in 0..frames {
+            paddle.update(FRAME);
+        }
+    }
+
+    #[test]
+    fn stays_in_lane() {
+        let mut paddle = Paddle::new(Side::Left).with_lane(0.0, 0.5);
+        assert_eq!(paddle.offset(), 0.25);
+        let lowest = 0.5 - paddle.length / 2.0;
+
+        paddle.move_to(0.9);
+        run(&mut paddle, 100);
+        assert!((paddle.offset() - lowest).abs() < 1e-4);
+
+        paddle.press(Direction::Down, true);
+        run(&mut paddle, 100);
+        assert_eq!(paddle.offset(), lowest);
+        assert_eq!(paddle.vel, 0.0);
+    }
+
+    #[test]
+    fn handicapped_speed() {
+        let mut paddle = Paddle::new(Side::Left).with_handicap(Handicap {
+            speed: 0.5,
+            ..Handicap::default()
+        });
+        paddle.move_to(0.9);
+        let mut top_speed: f32 = 0.0;
+        for _ in 0..200 {
+            paddle.update(FRAME);
+            top_speed = top_speed.max(paddle.vel.abs());
+            assert!(paddle.offset() <= 0.9 + 1e-6);
         }
+        assert!((top_speed - Paddle::SPEED * 0.5).abs() < 1e-6);
+        assert!((paddle.offset() - 0.9).abs() < 1e-4);
+        assert_eq!(paddle.vel, 0.0);
     }
 }
@@ -1,9 +1,13 @@
 use ratatui::{layout::Position, prelude::*};
 
+/// The height of a terminal cell relative to its width, assumed when the terminal doesn't report
+/// its size in pixels.
+pub const DEFAULT_CELL_ASPECT: f32 = 2.0;
+
 /// A coordinate point in the range [0.0, 1.0] representing a position in the game world.
 ///
 /// (0.5, 0.5) is the center of the screen.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -16,6 +20,11 @@ impl Point {
         Self { x, y }
     }
 
+    /// The point reflected across the vertical center line of the game world.
+    pub const fn mirrored(self) -> Self {
+        Self::new(1.0 - self.x, self.y)
+    }
+
     /// Convert a point in the game world to a point on the screen.
     ///
     /// Screen coordinates are in the range [0, width] and [0, height].
@@ -28,6 +37,146 @@ impl Point {
             y: (self.y * max_height) as u16 + area.y,
         }
     }
+
+    /// Convert a point on the screen to a point in the game world.
+    ///
+    /// This is the inverse of [`Point::to_screen`]. Positions outside the area are clamped to the
+    /// edges of the game world.
+    pub fn from_screen(position: Position, area: Rect) -> Self {
+        let max_width = (area.width.saturating_sub(1)).max(1) as f32;
+        let max_height = (area.height.saturating_sub(1)).max(1) as f32;
+        let x = position.x.saturating_sub(area.x) as f32 / max_width;
+        let y = position.y.saturating_sub(area.y) as f32 / max_height;
+        Self::new(x.clamp(0.0, 1.0), y.clamp(0.0, 1.0))
+    }
+}
+
+/// An axis-aligned rectangle in the game world, such as an obstacle.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Bounds {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub const fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub const fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn contains(&self, pos: Point) -> bool {
+        (self.x..self.right()).contains(&pos.x) && (self.y..self.bottom()).contains(&pos.y)
+    }
+
+    /// The bounds reflected across the vertical center line of the game world.
+    pub const fn mirrored(self) -> Self {
+        Self::new(1.0 - self.right(), self.y, self.width, self.height)
+    }
+
+    /// The cells covered by the bounds when the game world is drawn in the given area.
+    pub fn to_screen(self, area: Rect) -> Rect {
+        let (width, height) = (f32::from(area.width), f32::from(area.height));
+        let left = (self.x * width).floor().max(0.0) as u16;
+        let top = (self.y * height).floor().max(0.0) as u16;
+        let right = (self.right() * width).ceil().max(0.0) as u16;
+        let bottom = (self.bottom() * height).ceil().max(0.0) as u16;
+        Rect::new(
+            area.x + left,
+            area.y + top,
+            right.saturating_sub(left).max(1),
+            bottom.saturating_sub(top).max(1),
+        )
+        .intersection(area)
+    }
+}
+
+/// A side of the court, which is guarded by a paddle or is a wall the ball bounces off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// The direction a paddle lies in, along the side it guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Moves up and down, guarding the left or right side.
+    Vertical,
+    /// Moves left and right, guarding the top or bottom side.
+    Horizontal,
+}
+
+impl Side {
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+            Side::Top => "top",
+            Side::Bottom => "bottom",
+        }
+    }
+
+    pub const fn orientation(self) -> Orientation {
+        match self {
+            Side::Left | Side::Right => Orientation::Vertical,
+            Side::Top | Side::Bottom => Orientation::Horizontal,
+        }
+    }
+
+    /// The point in the middle of the side.
+    pub const fn center(self) -> Point {
+        match self {
+            Side::Left => Point::new(0.0, 0.5),
+            Side::Right => Point::new(1.0, 0.5),
+            Side::Top => Point::new(0.5, 0.0),
+            Side::Bottom => Point::new(0.5, 1.0),
+        }
+    }
+
+    /// Whether the point is beyond this side of the court.
+    pub fn is_beyond(self, pos: Point) -> bool {
+        match self {
+            Side::Left => pos.x < 0.0,
+            Side::Right => pos.x > 1.0,
+            Side::Top => pos.y < 0.0,
+            Side::Bottom => pos.y > 1.0,
+        }
+    }
+}
+
+impl Orientation {
+    /// The coordinate of the point along this direction: y for vertical and x for horizontal.
+    pub const fn along(self, pos: Point) -> f32 {
+        match self {
+            Orientation::Vertical => pos.y,
+            Orientation::Horizontal => pos.x,
+        }
+    }
+
+    pub fn along_mut(self, pos: &mut Point) -> &mut f32 {
+        match self {
+            Orientation::Vertical => &mut pos.y,
+            Orientation::Horizontal => &mut pos.x,
+        }
+    }
 }
 
 /// A velocity vector in the range [-1.0, 1.0] representing a direction and speed.
@@ -47,7 +196,7 @@ impl Point {
 ///
 /// - Vertical velocity: -0.69, -0.46, -0.23, 0.0, 0.23, 0.46, 0.69
 /// - Horizontal velocity: -0.53, -0.39. -0.26, 0.26, 0.39, 0.53
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
@@ -0,0 +1,256 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{capabilities::GlyphSupport, physics::Point};
+
+/// What a power-up does when the ball collects it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Makes the paddle of the player who last hit the ball longer.
+    LongPaddle,
+    /// Speeds up the ball for everyone.
+    FastBall,
+    /// Swaps the movement keys of the other team's players.
+    ReversedControls,
+    /// Splits the ball into three. The extra balls stay in play until they leave the court.
+    MultiBall,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::LongPaddle,
+        PowerUpKind::FastBall,
+        PowerUpKind::ReversedControls,
+        PowerUpKind::MultiBall,
+    ];
+
+    /// A short name for the power-up, shown next to its timer.
+    pub const fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::LongPaddle => "long",
+            PowerUpKind::FastBall => "fast ball",
+            PowerUpKind::ReversedControls => "reversed",
+            PowerUpKind::MultiBall => "multi-ball",
+        }
+    }
+
+    /// What happened when the named player collected the power-up.
+    pub fn message(self, name: &str) -> String {
+        match self {
+            PowerUpKind::LongPaddle => format!("{name} got a long paddle"),
+            PowerUpKind::FastBall => format!("{name} sped up the ball"),
+            PowerUpKind::ReversedControls => format!("{name} reversed the other team's controls"),
+            PowerUpKind::MultiBall => format!("{name} split the ball"),
+        }
+    }
+
+    /// The character the power-up is drawn with on the court.
+    pub const fn symbol(self, glyphs: GlyphSupport) -> &'static str {
+        match (self, glyphs) {
+            (PowerUpKind::LongPaddle, _) => "+",
+            (PowerUpKind::FastBall, GlyphSupport::Ascii) => ">",
+            (PowerUpKind::FastBall, _) => "»",
+            (PowerUpKind::ReversedControls, GlyphSupport::Ascii) => "~",
+            (PowerUpKind::ReversedControls, _) => "⇄",
+            (PowerUpKind::MultiBall, GlyphSupport::Ascii) => "*",
+            (PowerUpKind::MultiBall, _) => "⁂",
+        }
+    }
+}
+
+/// A power-up waiting on the court to be collected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub pos: Point,
+}
+
+/// A power-up that has been collected and is affecting the game until it runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivePowerUp {
+    pub kind: PowerUpKind,
+    /// The player slot affected, or `None` if it affects the whole game.
+    pub slot: Option<usize>,
+    pub until: Instant,
+}
+
+impl ActivePowerUp {
+    pub fn remaining(&self) -> Duration {
+        self.until.saturating_duration_since(Instant::now())
+    }
+}
+
+/// The power-ups in an arcade match: one waiting on the court at a time, and the ones in effect.
+#[derive(Debug)]
+pub struct PowerUps {
+    pub spawned: Option<PowerUp>,
+    pub active: Vec<ActivePowerUp>,
+    next_spawn: Instant,
+    rng: Rng,
+}
+
+impl Default for PowerUps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerUps {
+    // How long after a power-up is collected before the next one appears
+    const SPAWN_INTERVAL: Duration = Duration::from_secs(5);
+    /// How long each power-up lasts once collected.
+    pub const DURATION: Duration = Duration::from_secs(8);
+    // How close the ball needs to get to collect a power-up
+    const REACH: f32 = 0.04;
+
+    pub fn new() -> Self {
+        Self {
+            spawned: None,
+            active: Vec::new(),
+            next_spawn: Instant::now() + Self::SPAWN_INTERVAL,
+            rng: Rng::seeded(),
+        }
+    }
+
+    /// Spawn a power-up if one is due and remove the ones that have run out, returning them.
+    ///
+    /// Power-ups appear in the middle of the court, away from the paddles.
+    pub fn update(&mut self) -> Vec<ActivePowerUp> {
+        let now = Instant::now();
+        if self.spawned.is_none() && now >= self.next_spawn {
+            let kind = PowerUpKind::ALL[self.rng.below(PowerUpKind::ALL.len())];
+            let x = 0.25 + self.rng.next_f32() * 0.5;
+            let y = 0.15 + self.rng.next_f32() * 0.7;
+            self.spawned = Some(PowerUp {
+                kind,
+                pos: Point::new(x, y),
+            });
+        }
+        let (expired, active) = self.active.iter().partition(|active| active.until <= now);
+        self.active = active;
+        expired
+    }
+
+    /// Collect the waiting power-up if a ball at the given position has reached it.
+    pub fn collect(&mut self, ball: Point) -> Option<PowerUpKind> {
+        let power_up = self.spawned?;
+        if (ball.x - power_up.pos.x).hypot(ball.y - power_up.pos.y) >= Self::REACH {
+            return None;
+        }
+        self.spawned = None;
+        self.next_spawn = Instant::now() + Self::SPAWN_INTERVAL;
+        Some(power_up.kind)
+    }
+
+    /// Put a collected power-up into effect for each of the given slots, or the whole game for
+    /// `None`. Collecting the same power-up again restarts its timer.
+    pub fn activate(&mut self, kind: PowerUpKind, slots: &[Option<usize>]) {
+        let until = Instant::now() + Self::DURATION;
+        for &slot in slots {
+            self.active
+                .retain(|active| active.kind != kind || active.slot != slot);
+            self.active.push(ActivePowerUp { kind, slot, until });
+        }
+    }
+
+    /// Whether the given power-up is in effect for the slot, or the whole game for `None`.
+    pub fn is_active(&self, kind: PowerUpKind, slot: Option<usize>) -> bool {
+        self.active
+            .iter()
+            .any(|active| active.kind == kind && active.slot == slot)
+    }
+
+    /// Remove every power-up, ready for the next match.
+    pub fn clear(&mut self) {
+        *self = Self {
+            rng: self.rng,
+            ..Self::new()
+        };
+    }
+}
+
+/// A small xorshift random number generator, which is plenty for placing power-ups.
+#[derive(Debug, Clone, Copy)]
+struct Rng(u64);
+
+impl Rng {
+    fn seeded() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        // the state must not be zero
+        Self(nanos | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number from 0 up to but not including 1.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawned(kind: PowerUpKind) -> PowerUps {
+        PowerUps {
+            spawned: Some(PowerUp {
+                kind,
+                pos: Point::new(0.5, 0.5),
+            }),
+            ..PowerUps::new()
+        }
+    }
+
+    #[test]
+    fn collect() {
+        let mut power_ups = spawned(PowerUpKind::FastBall);
+        assert_eq!(power_ups.collect(Point::new(0.5, 0.55)), None);
+        assert_eq!(
+            power_ups.collect(Point::new(0.52, 0.52)),
+            Some(PowerUpKind::FastBall)
+        );
+        assert_eq!(power_ups.spawned, None);
+        assert_eq!(power_ups.collect(Point::new(0.5, 0.5)), None);
+        // the next one only appears after a while
+        assert!(power_ups.update().is_empty());
+        assert_eq!(power_ups.spawned, None);
+    }
+
+    #[test]
+    fn activate_and_expire() {
+        let mut power_ups = PowerUps::new();
+        power_ups.activate(PowerUpKind::ReversedControls, &[Some(1), Some(3)]);
+        power_ups.activate(PowerUpKind::FastBall, &[None]);
+        assert!(power_ups.is_active(PowerUpKind::ReversedControls, Some(3)));
+        assert!(!power_ups.is_active(PowerUpKind::ReversedControls, Some(0)));
+        assert!(power_ups.is_active(PowerUpKind::FastBall, None));
+        assert!(!power_ups.is_active(PowerUpKind::FastBall, Some(1)));
+
+        // collecting it again restarts the timer rather than adding another
+        power_ups.active[0].until = Instant::now();
+        power_ups.activate(PowerUpKind::ReversedControls, &[Some(1)]);
+        assert_eq!(power_ups.active.len(), 3);
+        assert!(power_ups
+            .active
+            .iter()
+            .all(|active| active.remaining() > Duration::ZERO));
+
+        power_ups.active[0].until = Instant::now();
+        let expired = power_ups.update();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].slot, Some(3));
+        assert!(!power_ups.is_active(PowerUpKind::ReversedControls, Some(3)));
+        assert!(power_ups.is_active(PowerUpKind::ReversedControls, Some(1)));
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{Context, OptionExt},
+    Result,
+};
+use tracing::{info, warn};
+
+use crate::{
+    ball::BallGlyphs,
+    keymap::{self, Action, Keymap},
+    viewer::Preferences,
+};
+
+/// The directory where the server stores its host key and player profiles.
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(dirs::config_local_dir()
+        .ok_or_eyre("Failed to get config local dir")?
+        .join("pong_russh"))
+}
+
+/// A player's settings, persisted across connections.
+///
+/// Players are identified by the fingerprint of the public key they authenticate with, so their
+/// settings follow them regardless of the user name they connect as.
+///
+/// Profiles are stored as simple `key = value` lines, with key bindings stored as space separated
+/// key names, e.g. `key.up = i Up`. Only bindings that differ from the defaults are stored.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub identity: String,
+    pub name: String,
+    pub keymap: Keymap,
+    pub preferences: Preferences,
+    /// The most times in a row the player has returned the ball in practice.
+    pub practice_best: u32,
+}
+
+impl Profile {
+    /// Load the profile for the given identity, or create a default profile if there is none.
+    ///
+    /// Unreadable profiles are logged and replaced with the defaults rather than preventing the
+    /// player from connecting.
+    pub fn load(identity: &str, name: &str) -> Self {
+        let mut profile = Self {
+            identity: identity.to_string(),
+            name: name.to_string(),
+            keymap: Keymap::default(),
+            preferences: Preferences::default(),
+            practice_best: 0,
+        };
+        let path = match Self::path(identity) {
+            Ok(path) if path.exists() => path,
+            Ok(path) => match Self::legacy_path(&path, identity) {
+                Some(legacy) if legacy.exists() => legacy,
+                _ => return profile,
+            },
+            Err(err) => {
+                warn!("Failed to locate profile: {err}");
+                return profile;
+            }
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                info!("Loading profile from {}", path.display());
+                profile.parse(&contents);
+            }
+            Err(err) => warn!("Failed to read profile from {}: {err}", path.display()),
+        }
+        profile
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path(&self.identity)?;
+        fs::create_dir_all(path.parent().unwrap())
+            .wrap_err("Failed to create directory for profiles")?;
+        fs::write(&path, self.to_string()).wrap_err("Failed to write profile to file")?;
+        info!("Saved profile to {}", path.display());
+        Ok(())
+    }
+
+    fn path(identity: &str) -> Result<PathBuf> {
+        Ok(config_dir()?.join("players").join(file_name(identity)))
+    }
+
+    /// Where the profile was saved before `+` and `/` were told apart in file names, if that
+    /// differs from where it is saved now.
+    fn legacy_path(path: &Path, identity: &str) -> Option<PathBuf> {
+        identity
+            .contains('+')
+            .then(|| path.with_file_name(identity.replace([':', '/', '+'], "_")))
+    }
+
+    fn parse(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "mirror" => self.preferences.mirror = value == "true",
+                "own_color" => {
+                    if let Ok(color) = value.parse() {
+                        self.preferences.own_color = color;
+                    }
+                }
+                "ball_glyphs" => {
+                    if let Some(glyphs) = BallGlyphs::from_name(value) {
+                        self.preferences.ball_glyphs = glyphs;
+                    }
+                }
+                "theme" => self.preferences.theme = value.to_string(),
+                "graphics" => self.preferences.graphics = value == "true",
+                "court_markings" => self.preferences.court_markings = value == "true",
+                "trail" => self.preferences.trail = value == "true",
+                "hit_flash" => self.preferences.hit_flash = value == "true",
+                "score_shake" => self.preferences.score_shake = value == "true",
+                "muted" => self.preferences.muted = value == "true",
+                "bell_paddle_hit" => self.preferences.bell_paddle_hit = value == "true",
+                "bell_wall_bounce" => self.preferences.bell_wall_bounce = value == "true",
+                "bell_point" => self.preferences.bell_point = value == "true",
+                "bell_match" => self.preferences.bell_match = value == "true",
+                "desktop_notifications" => self.preferences.desktop_notifications = value == "true",
+                "practice_best" => self.practice_best = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+            if let Some(action) = key.strip_prefix("key.").and_then(Action::from_name) {
+                let keys: Vec<_> = value
+                    .split_whitespace()
+                    .filter_map(keymap::parse_key)
+                    .collect();
+                self.keymap.bind(action, &keys);
+            }
+        }
+    }
+}
+
+/// The file name of the profile for the given identity.
+///
+/// Fingerprints look like `SHA256:base64`, which contains characters that can't be used in file
+/// names on some platforms, so the base64 is written in its URL-safe form with `-` and `_`.
+fn file_name(identity: &str) -> String {
+    identity.replace([':', '/'], "_").replace('+', "-")
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "name = {}", self.name)?;
+        writeln!(f, "mirror = {}", self.preferences.mirror)?;
+        writeln!(f, "own_color = {}", self.preferences.own_color)?;
+        writeln!(f, "ball_glyphs = {}", self.preferences.ball_glyphs.name())?;
+        writeln!(f, "theme = {}", self.preferences.theme)?;
+        writeln!(f, "graphics = {}", self.preferences.graphics)?;
+        writeln!(f, "court_markings = {}", self.preferences.court_markings)?;
+        writeln!(f, "trail = {}", self.preferences.trail)?;
+        writeln!(f, "hit_flash = {}", self.preferences.hit_flash)?;
+        writeln!(f, "score_shake = {}", self.preferences.score_shake)?;
+        writeln!(f, "muted = {}", self.preferences.muted)?;
+        writeln!(f, "bell_paddle_hit = {}", self.preferences.bell_paddle_hit)?;
+        writeln!(
+            f,
+            "bell_wall_bounce = {}",
+            self.preferences.bell_wall_bounce
+        )?;
+        writeln!(f, "bell_point = {}", self.preferences.bell_point)?;
+        writeln!(f, "bell_match = {}", self.preferences.bell_match)?;
+        writeln!(
+            f,
+            "desktop_notifications = {}",
+            self.preferences.desktop_notifications
+        )?;
+        writeln!(f, "practice_best = {}", self.practice_best)?;
+        for (action, keys) in self.keymap.overrides() {
+            let keys: Vec<_> = keys.iter().copied().map(keymap::key_name).collect();
+            writeln!(f, "key.{} = {}", action.name(), keys.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use super::*;
+
+    #[test]
+    fn file_names_are_distinct() {
+        assert_eq!(file_name("SHA256:ab+c/d"), "SHA256_ab-c_d");
+        assert_ne!(file_name("SHA256:a+b"), file_name("SHA256:a/b"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut profile = Profile {
+            identity: "SHA256:abc".to_string(),
+            name: "alice".to_string(),
+            practice_best: 12,
+            ..Profile::default()
+        };
+        profile.preferences.mirror = true;
+        profile.preferences.theme = "phosphor".to_string();
+        profile.preferences.bell_point = false;
+        assert!(profile
+            .keymap
+            .bind(Action::Up, &[KeyCode::Char('i'), KeyCode::F(2)]));
+        assert!(profile.keymap.bind(Action::Help, &[KeyCode::Char('o')]));
+        assert!(profile.keymap.bind(Action::Settings, &[KeyCode::Char('p')]));
+
+        let mut loaded = Profile {
+            identity: profile.identity.clone(),
+            name: profile.name.clone(),
+            ..Profile::default()
+        };
+        loaded.parse(&profile.to_string());
+        assert_eq!(loaded.keymap, profile.keymap);
+        assert_eq!(loaded.preferences, profile.preferences);
+        assert_eq!(loaded.practice_best, 12);
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tracing::{info, warn};
+
+use crate::{
+    event::{self, GameEvent},
+    game::Game,
+    tournament::{Entry, Tournament, TournamentError},
+    viewer::Role,
+};
+
+/// Where a client is on the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// Playing or watching the game in the given room.
+    Room(usize),
+    /// Watching the bracket of the named tournament between matches.
+    Bracket(String),
+}
+
+/// A game being played on the server.
+#[derive(Debug)]
+struct Room {
+    game: Game,
+    events: broadcast::Receiver<GameEvent>,
+    /// The tournament and the index of the bracket match played in the room, if any.
+    tournament_match: Option<(String, usize)>,
+}
+
+impl Room {
+    fn new(game: Game, tournament_match: Option<(String, usize)>) -> Self {
+        tokio::spawn(event::log_events(game.subscribe()));
+        Self {
+            events: game.subscribe(),
+            game,
+            tournament_match,
+        }
+    }
+}
+
+/// The games being played on the server, the tournaments organizing some of them, and where each
+/// client is.
+///
+/// Everyone plays in the main room unless they organize or enter a tournament, which plays each
+/// of its matches in a room of its own.
+#[derive(Debug)]
+pub struct Rooms {
+    rooms: BTreeMap<usize, Room>,
+    next_room: usize,
+    locations: HashMap<usize, Location>,
+    tournaments: BTreeMap<String, Tournament>,
+    // The rooms of tournament matches that have ended since they were last scheduled, with the
+    // winning team and the score if the match was played out
+    finished: Vec<(usize, usize, Option<[u32; 2]>)>,
+}
+
+impl Default for Rooms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rooms {
+    /// The room everyone joins unless they enter a tournament.
+    pub const MAIN: usize = 0;
+
+    pub fn new() -> Self {
+        let mut rooms = BTreeMap::new();
+        rooms.insert(Self::MAIN, Room::new(Game::new(), None));
+        Self {
+            rooms,
+            next_room: Self::MAIN + 1,
+            locations: HashMap::new(),
+            tournaments: BTreeMap::new(),
+            finished: Vec::new(),
+        }
+    }
+
+    /// Move the client into the main room, returning its game for them to join.
+    pub fn join_main(&mut self, client_id: usize) -> &mut Game {
+        self.locations.insert(client_id, Location::Room(Self::MAIN));
+        &mut self.main_room().game
+    }
+
+    fn main_room(&mut self) -> &mut Room {
+        self.rooms
+            .get_mut(&Self::MAIN)
+            .expect("the main room is never removed")
+    }
+
+    /// Where the client is, if they have joined anything yet.
+    pub fn location(&self, client_id: usize) -> Option<&Location> {
+        self.locations.get(&client_id)
+    }
+
+    pub fn game(&self, room: usize) -> Option<&Game> {
+        self.rooms.get(&room).map(|room| &room.game)
+    }
+
+    /// The game the client is in, if they are in a room rather than watching a bracket.
+    pub fn game_mut(&mut self, client_id: usize) -> Option<&mut Game> {
+        match self.locations.get(&client_id)? {
+            Location::Room(room) => self.rooms.get_mut(room).map(|room| &mut room.game),
+            Location::Bracket(_) => None,
+        }
+    }
+
+    pub fn tournament(&self, name: &str) -> Option<&Tournament> {
+        self.tournaments.get(name)
+    }
+
+    /// Add a tournament organized by the given client, who watches its bracket.
+    pub fn create_tournament(
+        &mut self,
+        tournament: Tournament,
+        client_id: usize,
+    ) -> Result<(), TournamentError> {
+        let name = tournament.name.clone();
+        if self.tournaments.contains_key(&name) {
+            return Err(TournamentError::Exists(name));
+        }
+        info!(client_id, tournament = name, "Creating tournament");
+        self.tournaments.insert(name.clone(), tournament);
+        self.locations.insert(client_id, Location::Bracket(name));
+        Ok(())
+    }
+
+    /// Enter the client into the named tournament, returning their seed.
+    pub fn enter_tournament(
+        &mut self,
+        name: &str,
+        client_id: usize,
+        identity: &str,
+        player_name: &str,
+    ) -> Result<usize, TournamentError> {
+        let tournament = self
+            .tournaments
+            .get_mut(name)
+            .ok_or_else(|| TournamentError::NotFound(name.to_string()))?;
+        let seed = tournament.enter(identity, player_name, client_id)?;
+        info!(client_id, tournament = name, seed, "Entered tournament");
+        self.locations
+            .insert(client_id, Location::Bracket(name.to_string()));
+        Ok(seed)
+    }
+
+    /// Remove the client from wherever they are.
+    ///
+    /// A player who leaves a tournament match forfeits it, and one who leaves the bracket loses
+    /// their next match by walkover unless they come back in time. Tournaments are removed once
+    /// nobody is left following them.
+    pub fn leave(&mut self, client_id: usize) {
+        let tournament = match self.locations.remove(&client_id) {
+            Some(Location::Room(id)) => {
+                let Some(room) = self.rooms.get_mut(&id) else {
+                    return;
+                };
+                let role = room.game.role(client_id);
+                room.game.disconnect_player(client_id);
+                let Some((name, _)) = room.tournament_match.clone() else {
+                    return;
+                };
+                if let Role::Player(slot) = role {
+                    // the other player wins by forfeit
+                    self.finished.push((id, 1 - slot, None));
+                }
+                name
+            }
+            Some(Location::Bracket(name)) => name,
+            None => return,
+        };
+        if let Some(entrant) = self.tournaments.get_mut(&tournament).and_then(|t| {
+            t.entrants
+                .iter_mut()
+                .find(|e| e.client_id == Some(client_id))
+        }) {
+            entrant.client_id = None;
+            entrant.left = Some(Instant::now());
+        }
+        if self.following(&tournament).is_empty() {
+            info!(tournament, "Removing tournament");
+            self.tournaments.remove(&tournament);
+        }
+    }
+
+    /// Advance every game to the current time, returning what happened in each room.
+    pub fn update(&mut self) -> HashMap<usize, Vec<GameEvent>> {
+        let mut events = HashMap::new();
+        for (&id, room) in &mut self.rooms {
+            room.game.update();
+            let drained = drain_events(&mut room.events);
+            if room.tournament_match.is_some() {
+                for event in &drained {
+                    if let GameEvent::MatchEnded { winner, score } = event {
+                        self.finished
+                            .push((id, *winner, Some([score[0], score[1]])));
+                    }
+                }
+            }
+            events.insert(id, drained);
+        }
+        events
+    }
+
+    /// Record the results of tournament matches that ended or were forfeited, and start the
+    /// matches whose players are now available.
+    ///
+    /// Called after the clients have seen the last update, so the players see how their match
+    /// ended before they go back to the bracket. Returns messages for the clients involved.
+    pub fn schedule(&mut self) -> Vec<(usize, String)> {
+        let mut messages = Vec::new();
+        for (room, winner, score) in std::mem::take(&mut self.finished) {
+            messages.extend(self.finish_match(room, winner, score));
+        }
+        let names: Vec<_> = self.tournaments.keys().cloned().collect();
+        for name in names {
+            messages.extend(self.start_matches(&name));
+        }
+        messages
+    }
+
+    /// Record the result of the tournament match in the given room, sending the players back to
+    /// the bracket and closing the room.
+    ///
+    /// `winner` is the winning team, which is also their slot as tournaments are played in
+    /// classic mode.
+    fn finish_match(
+        &mut self,
+        room: usize,
+        winner: usize,
+        score: Option<[u32; 2]>,
+    ) -> Vec<(usize, String)> {
+        let Some(Room {
+            game,
+            tournament_match: Some((name, index)),
+            ..
+        }) = self.rooms.remove(&room)
+        else {
+            return Vec::new();
+        };
+        let Some(tournament) = self.tournaments.get_mut(&name) else {
+            return Vec::new();
+        };
+        let seeds = tournament.bracket.entries(index).map(|entry| match entry {
+            Entry::Entrant(seed) => seed,
+            _ => unreachable!("only matches between two entrants are played"),
+        });
+        tournament.bracket.record(index, seeds[winner], score);
+        info!(
+            tournament = name,
+            index,
+            winner = seeds[winner],
+            ?score,
+            "Tournament match ended"
+        );
+        for client_id in (0..2).filter_map(|slot| game.player(slot).map(|p| p.client_id)) {
+            self.locations
+                .insert(client_id, Location::Bracket(name.clone()));
+        }
+        let Some(champion) = tournament.bracket.champion() else {
+            return Vec::new();
+        };
+        let message = format!("{} won the tournament", tournament.entrant_name(champion));
+        self.following(&name)
+            .into_iter()
+            .map(|client_id| (client_id, message.clone()))
+            .collect()
+    }
+
+    /// Start each ready match in the named tournament whose players are both waiting at the
+    /// bracket, or give it to one player by walkover if the other disconnected and didn't come
+    /// back in time.
+    fn start_matches(&mut self, name: &str) -> Vec<(usize, String)> {
+        let mut messages = Vec::new();
+        let Some(tournament) = self.tournaments.get_mut(name) else {
+            return messages;
+        };
+        let now = Instant::now();
+        for (index, seeds) in tournament.bracket.ready() {
+            let walked_over = seeds.map(|seed| {
+                tournament
+                    .entrants
+                    .get(seed)
+                    .is_some_and(|entrant| entrant.walked_over(now))
+            });
+            if walked_over.contains(&true) {
+                // if neither came back, the first player goes through, who is the higher seed or
+                // comes from the winners bracket
+                let winner = usize::from(walked_over[0] && !walked_over[1]);
+                let names = seeds.map(|seed| tournament.entrant_name(seed));
+                info!(
+                    tournament = name,
+                    index,
+                    winner = seeds[winner],
+                    "Tournament match walked over"
+                );
+                tournament.bracket.record(index, seeds[winner], None);
+                if let Some(client_id) = tournament.entrants[seeds[winner]].client_id {
+                    messages.push((
+                        client_id,
+                        format!("{} didn't come back, so you go through", names[1 - winner]),
+                    ));
+                }
+                continue;
+            }
+            let waiting = seeds.map(|seed| {
+                tournament
+                    .entrants
+                    .get(seed)
+                    .and_then(|entrant| entrant.client_id)
+                    .filter(|client_id| {
+                        self.locations.get(client_id) == Some(&Location::Bracket(name.to_string()))
+                    })
+            });
+            let [Some(first), Some(second)] = waiting else {
+                continue;
+            };
+            let mut game = Game::with_config(tournament.config.clone());
+            let names = seeds.map(|seed| tournament.entrant_name(seed));
+            for (client_id, player_name) in [first, second].into_iter().zip(&names) {
+                game.connect_player(client_id, player_name);
+            }
+            tournament.bracket.matches[index].playing = true;
+            let id = self.next_room;
+            self.next_room += 1;
+            info!(
+                tournament = name,
+                index,
+                room = id,
+                "Starting tournament match"
+            );
+            self.rooms
+                .insert(id, Room::new(game, Some((name.to_string(), index))));
+            self.locations.insert(first, Location::Room(id));
+            self.locations.insert(second, Location::Room(id));
+            messages.push((
+                first,
+                format!("Your match against {} is starting", names[1]),
+            ));
+            messages.push((
+                second,
+                format!("Your match against {} is starting", names[0]),
+            ));
+        }
+        messages
+    }
+
+    /// The clients at the named tournament's bracket or playing in its matches.
+    fn following(&self, name: &str) -> Vec<usize> {
+        self.locations
+            .iter()
+            .filter(|(_, location)| match location {
+                Location::Bracket(bracket) => bracket == name,
+                Location::Room(id) => self.rooms.get(id).is_some_and(|room| {
+                    room.tournament_match
+                        .as_ref()
+                        .is_some_and(|(tournament, _)| tournament == name)
+                }),
+            })
+            .map(|(&client_id, _)| client_id)
+            .collect()
+    }
+}
+
+/// Take every event published since the last call without waiting.
+fn drain_events(events: &mut broadcast::Receiver<GameEvent>) -> Vec<GameEvent> {
+    let mut drained = Vec::new();
+    loop {
+        match events.try_recv() {
+            Ok(event) => drained.push(event),
+            Err(TryRecvError::Lagged(skipped)) => warn!("Clients missed {skipped} game events"),
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return drained,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        mode::MatchConfig,
+        tournament::{Elimination, Entrant},
+    };
+
+    #[tokio::test]
+    async fn walkover_after_leaving_the_bracket() {
+        let mut rooms = Rooms::new();
+        let tournament = Tournament::new("cup", Elimination::Single, 2, MatchConfig::default());
+        rooms.create_tournament(tournament, 0).unwrap();
+        rooms.enter_tournament("cup", 1, "first", "first").unwrap();
+        rooms
+            .enter_tournament("cup", 2, "second", "second")
+            .unwrap();
+        rooms.leave(2);
+        assert_eq!(rooms.schedule(), vec![]);
+        assert!(rooms
+            .tournament("cup")
+            .unwrap()
+            .bracket
+            .champion()
+            .is_none());
+
+        // still waiting until the timeout, when the player who stayed goes through
+        let entrant = &mut rooms.tournaments.get_mut("cup").unwrap().entrants[1];
+        entrant.left =
+            Instant::now().checked_sub(Entrant::WALKOVER_TIMEOUT - Duration::from_secs(1));
+        assert_eq!(rooms.schedule(), vec![]);
+        let entrant = &mut rooms.tournaments.get_mut("cup").unwrap().entrants[1];
+        entrant.left = Instant::now().checked_sub(Entrant::WALKOVER_TIMEOUT);
+        assert_eq!(
+            rooms.schedule(),
+            vec![(1, "second didn't come back, so you go through".to_string())]
+        );
+        assert_eq!(rooms.tournament("cup").unwrap().bracket.champion(), Some(0));
+        assert_eq!(
+            rooms.location(1),
+            Some(&Location::Bracket("cup".to_string()))
+        );
+    }
+}
@@ -1,58 +1,122 @@
 use std::{
     collections::HashMap,
+    io,
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use color_eyre::{
-    eyre::{Context, OptionExt},
-    Result,
+use color_eyre::{eyre::Context, Result};
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, MouseEvent,
+    MouseEventKind,
+};
+use ratatui::{
+    layout::{Position, Rect},
+    Terminal,
 };
-use ratatui::Terminal;
 use russh::{
     keys::{
         ssh_key::{rand_core::OsRng, Algorithm, LineEnding},
-        PrivateKey, PublicKey,
+        HashAlg, PrivateKey, PublicKey,
     },
-    server::{Auth, Config, Handler, Msg, Server, Session},
+    server::{Auth, Config, Handle, Handler, Msg, Server, Session},
     Channel, ChannelId, Pty,
 };
 use tokio::{sync::Mutex, time::sleep};
-use tracing::{info, instrument};
+use tracing::{debug, info, instrument, warn};
 
-use crate::{backend::SshBackend, game::Game};
+use crate::{
+    backend::SshBackend,
+    capabilities::Capabilities,
+    client::Client,
+    input::{InputDecoder, InputEvent},
+    keymap::Action,
+    level::Levels,
+    mode::MatchConfig,
+    paddle::Direction,
+    profile::{self, Profile},
+    room::{Location, Rooms},
+    settings::{SettingsOutcome, SettingsScreen},
+    theme::Themes,
+    tournament::TournamentRequest,
+    viewer::Role,
+};
 
 pub type SshTerminal = Terminal<SshBackend>;
 
 #[derive(Debug, Clone)]
 pub struct AppServer {
     client_counter: usize,
-    game: Arc<Mutex<Game>>,
-    terminals: Arc<Mutex<HashMap<usize, SshTerminal>>>,
+    rooms: Arc<Mutex<Rooms>>,
+    clients: Arc<Mutex<HashMap<usize, Client>>>,
+    themes: Arc<Themes>,
+    levels: Arc<Levels>,
     key: PrivateKey,
 }
 
 impl AppServer {
     pub fn new() -> Result<Self> {
         let key = load_or_generate_key()?;
+        let themes = Themes::load()?;
+        let levels = Levels::load()?;
         Ok(Self {
             client_counter: 0,
-            game: Arc::new(Mutex::new(Game::new())),
-            terminals: Arc::new(Mutex::new(HashMap::new())),
+            rooms: Arc::new(Mutex::new(Rooms::new())),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            themes: Arc::new(themes),
+            levels: Arc::new(levels),
             key,
         })
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        let game = self.game.clone();
-        let terminals = self.terminals.clone();
+        let rooms = self.rooms.clone();
+        let clients = self.clients.clone();
         tokio::spawn(async move {
             loop {
                 sleep(tokio::time::Duration::from_millis(16)).await;
-                game.lock().await.update();
-                for terminal in terminals.lock().await.values_mut() {
-                    game.lock().await.draw(terminal).unwrap();
+                let events = rooms.lock().await.update();
+                for client in clients.lock().await.values_mut() {
+                    if let Err(err) = client.probe_latency() {
+                        warn!(client_id = ?client.client_id, "Failed to probe latency: {err}");
+                    }
+                    let mut rooms = rooms.lock().await;
+                    // players keep their latency as they move between rooms
+                    if let (Some(latency), Some(game)) =
+                        (client.latency.latency(), rooms.game_mut(client.client_id))
+                    {
+                        game.set_latency(client.client_id, latency);
+                    }
+                    let room = match rooms.location(client.client_id) {
+                        Some(Location::Bracket(name)) => {
+                            if let Some(tournament) = rooms.tournament(name) {
+                                client.draw_bracket(tournament).unwrap();
+                            }
+                            continue;
+                        }
+                        Some(Location::Room(room)) => *room,
+                        // clients watch the main room until they have joined
+                        None => Rooms::MAIN,
+                    };
+                    let Some(game) = rooms.game(room) else {
+                        continue;
+                    };
+                    for event in events.get(&room).into_iter().flatten() {
+                        if let Err(err) = client.handle_event(event, game) {
+                            warn!(client_id = ?client.client_id, "Failed to send sound: {err}");
+                        }
+                    }
+                    client.draw(game).unwrap();
+                }
+                let messages = rooms.lock().await.schedule();
+                if !messages.is_empty() {
+                    let mut clients = clients.lock().await;
+                    for (client_id, message) in messages {
+                        if let Some(client) = clients.get_mut(&client_id) {
+                            client.notifications.push(message);
+                        }
+                    }
                 }
             }
         });
@@ -74,10 +138,7 @@ impl AppServer {
 }
 
 fn load_or_generate_key() -> Result<PrivateKey> {
-    let path = dirs::config_local_dir()
-        .ok_or_eyre("Failed to get config local dir")?
-        .join("pong_russh")
-        .join("host_key");
+    let path = profile::config_dir()?.join("host_key");
     let key = if path.exists() {
         info!("Loading host key from {}", path.display());
         PrivateKey::read_openssh_file(&path).wrap_err("Failed to read host key from file")?
@@ -97,6 +158,96 @@ fn load_or_generate_key() -> Result<PrivateKey> {
     Ok(key)
 }
 
+/// Acts on a client's key presses.
+///
+/// This is separate from the [`AppHandler`] so that a lone escape can be handled by a task once
+/// the escape key timeout passes, as well as when input arrives.
+#[derive(Debug, Clone)]
+struct KeyHandler {
+    client_id: usize,
+    rooms: Arc<Mutex<Rooms>>,
+    clients: Arc<Mutex<HashMap<usize, Client>>>,
+    // Whether the client's terminal reports key releases
+    release_events: bool,
+}
+
+impl KeyHandler {
+    /// Act on a key press, returning whether the player quit, in which case the caller closes
+    /// the channel.
+    async fn handle(&self, key: KeyEvent) -> Result<bool> {
+        let mut clients = self.clients.lock().await;
+        let Some(client) = clients.get_mut(&self.client_id) else {
+            return Ok(false);
+        };
+        // Ctrl+C always quits, so players can't lock themselves in by rebinding keys
+        let is_ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        let action = if is_ctrl_c {
+            Some(Action::Quit)
+        } else if let Some(settings) = client.settings.as_mut() {
+            if settings.handle_key(key, &mut client.profile, &client.themes)
+                == SettingsOutcome::Close
+            {
+                client.settings = None;
+                match client.profile.save() {
+                    Ok(()) => client.notifications.push("Settings saved"),
+                    Err(err) => {
+                        warn!(client_id = ?self.client_id, "Failed to save profile: {err}");
+                        client.notifications.push("Failed to save settings");
+                    }
+                }
+            }
+            return Ok(false);
+        } else {
+            client.profile.keymap.action(key.code)
+        };
+        let direction = match action {
+            Some(Action::Quit) if key.kind == KeyEventKind::Press => {
+                client.terminal.backend_mut().restore_session()?;
+                return Ok(true);
+            }
+            Some(Action::Settings) if key.kind == KeyEventKind::Press => {
+                client.settings = Some(SettingsScreen::default());
+                return Ok(false);
+            }
+            Some(Action::Help) if key.kind == KeyEventKind::Press => {
+                client.show_help = !client.show_help;
+                return Ok(false);
+            }
+            Some(Action::Mute) if key.kind == KeyEventKind::Press => {
+                let preferences = &mut client.profile.preferences;
+                preferences.muted = !preferences.muted;
+                client.notifications.push(if preferences.muted {
+                    "Sounds muted"
+                } else {
+                    "Sounds on"
+                });
+                if let Err(err) = client.profile.save() {
+                    warn!(client_id = ?self.client_id, "Failed to save profile: {err}");
+                }
+                return Ok(false);
+            }
+            Some(Action::Up) => Direction::Up,
+            Some(Action::Down) => Direction::Down,
+            Some(Action::Left) => Direction::Left,
+            Some(Action::Right) => Direction::Right,
+            _ => return Ok(false),
+        };
+        drop(clients);
+        let mut rooms = self.rooms.lock().await;
+        let Some(game) = rooms.game_mut(self.client_id) else {
+            return Ok(false);
+        };
+        match key.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                game.press(self.client_id, direction, self.release_events)
+            }
+            KeyEventKind::Release => game.release(self.client_id, direction),
+        }
+        Ok(false)
+    }
+}
+
 impl Server for AppServer {
     type Handler = AppHandler;
     fn new_client(&mut self, _peer_addr: Option<SocketAddr>) -> AppHandler {
@@ -104,8 +255,10 @@ impl Server for AppServer {
         info!("New client connected: {}", self.client_counter);
         AppHandler::new(
             self.client_counter,
-            self.game.clone(),
-            self.terminals.clone(),
+            self.rooms.clone(),
+            self.clients.clone(),
+            self.themes.clone(),
+            self.levels.clone(),
         )
     }
 }
@@ -113,34 +266,209 @@ impl Server for AppServer {
 #[derive(Debug)]
 pub struct AppHandler {
     pub client_id: usize,
-    pub game: Arc<Mutex<Game>>,
-    pub terminals: Arc<Mutex<HashMap<usize, SshTerminal>>>,
+    pub rooms: Arc<Mutex<Rooms>>,
+    pub clients: Arc<Mutex<HashMap<usize, Client>>>,
+    themes: Arc<Themes>,
+    levels: Arc<Levels>,
+    // Loaded when the client authenticates and moved into the client once it has a terminal
+    profile: Option<Profile>,
+    input: Arc<Mutex<InputDecoder>>,
+    keys: KeyHandler,
 }
 
 impl AppHandler {
     pub fn new(
         id: usize,
-        game: Arc<Mutex<Game>>,
-        terminals: Arc<Mutex<HashMap<usize, SshTerminal>>>,
+        rooms: Arc<Mutex<Rooms>>,
+        clients: Arc<Mutex<HashMap<usize, Client>>>,
+        themes: Arc<Themes>,
+        levels: Arc<Levels>,
     ) -> Self {
         Self {
             client_id: id,
-            game,
-            terminals,
+            keys: KeyHandler {
+                client_id: id,
+                rooms: rooms.clone(),
+                clients: clients.clone(),
+                release_events: false,
+            },
+            rooms,
+            clients,
+            themes,
+            levels,
+            profile: None,
+            input: Arc::new(Mutex::new(InputDecoder::default())),
         }
     }
+
+    /// Join the game as a player if there is a free slot, or as a spectator, and tell the client
+    /// which, along with any other notifications.
+    ///
+    /// The first player to join an empty game chooses the match options, and everyone else
+    /// joins the match already being played.
+    async fn join(&mut self, config: MatchConfig, mut notifications: Vec<String>) {
+        let profile = self.current_profile().await;
+        let mut rooms = self.rooms.lock().await;
+        let game = rooms.join_main(self.client_id);
+        if game.is_empty() {
+            info!(client_id = ?self.client_id, ?config, "Starting a new match");
+            game.configure(config);
+        } else if config != *game.config() && config != MatchConfig::default() {
+            notifications.push(format!(
+                "Joining the {} match already being played",
+                game.config().mode.name()
+            ));
+        }
+        let role = game.connect_player(self.client_id, &profile.name);
+        game.set_practice_best(self.client_id, profile.practice_best);
+        notifications.push(match role {
+            Role::Player(slot) => {
+                let position = game.config().mode.position_name(slot);
+                format!("You are playing on {position}")
+            }
+            Role::Spectator => "The game is full, so you are spectating".to_string(),
+        });
+        drop(rooms);
+        self.notify(notifications).await;
+    }
+
+    /// Organize or enter a tournament, or join the main room instead if that isn't possible.
+    async fn join_tournament(&mut self, request: TournamentRequest) {
+        let profile = self.current_profile().await;
+        let mut rooms = self.rooms.lock().await;
+        let result = match request {
+            TournamentRequest::Create(tournament) => {
+                let name = tournament.name.clone();
+                rooms
+                    .create_tournament(*tournament, self.client_id)
+                    .map(|()| format!("Players can enter with tournament={name}"))
+            }
+            TournamentRequest::Enter(name) => rooms
+                .enter_tournament(&name, self.client_id, &profile.identity, &profile.name)
+                .map(|seed| format!("You entered {name} as player {}", seed + 1)),
+        };
+        drop(rooms);
+        match result {
+            Ok(message) => self.notify(vec![message]).await,
+            Err(err) => {
+                warn!(client_id = ?self.client_id, "Failed to join tournament: {err}");
+                self.join(MatchConfig::default(), vec![err.to_string()])
+                    .await;
+            }
+        }
+    }
+
+    /// A copy of the client's profile.
+    async fn current_profile(&self) -> Profile {
+        // the profile has moved into the client if the terminal has been created
+        let clients = self.clients.lock().await;
+        let profile = clients
+            .get(&self.client_id)
+            .map(|client| &client.profile)
+            .or(self.profile.as_ref());
+        profile.cloned().unwrap_or_else(|| Profile {
+            name: "anonymous".to_string(),
+            ..Profile::default()
+        })
+    }
+
+    async fn notify(&self, notifications: Vec<String>) {
+        if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+            for message in notifications {
+                client.notifications.push(message);
+            }
+        }
+    }
+
+    /// Record the round trip time of a status query the terminal has just answered, warning the
+    /// player when it is high.
+    ///
+    /// The render loop shows the latency in the HUD of whichever game the player is in.
+    async fn record_latency(&self) {
+        let mut clients = self.clients.lock().await;
+        let Some(client) = clients.get_mut(&self.client_id) else {
+            return;
+        };
+        let Some(measurement) = client.latency.answer(Instant::now()) else {
+            return;
+        };
+        debug!(
+            client_id = ?self.client_id,
+            round_trip = ?measurement.round_trip,
+            latency = ?measurement.smoothed,
+            "Measured latency"
+        );
+        if measurement.became_high {
+            warn!(client_id = ?self.client_id, latency = ?measurement.smoothed, "High latency");
+            client.notifications.push(format!(
+                "High latency: {}ms, the game may feel sluggish",
+                measurement.smoothed.as_millis()
+            ));
+        }
+    }
+
+    /// Handle a lone escape as the escape key if nothing follows it before the timeout.
+    fn flush_escape_later(&self, channel_id: ChannelId, handle: Handle) {
+        let input = self.input.clone();
+        let keys = self.keys.clone();
+        tokio::spawn(async move {
+            sleep(InputDecoder::ESCAPE_TIMEOUT).await;
+            let Some(InputEvent::Key(key)) = input.lock().await.flush_escape(Instant::now()) else {
+                return;
+            };
+            match keys.handle(key).await {
+                Ok(true) => {
+                    let _ = handle.close(channel_id).await;
+                }
+                Ok(false) => {}
+                Err(err) => warn!(client_id = ?keys.client_id, "Failed to handle escape: {err}"),
+            }
+        });
+    }
+
+    /// Dragging or hovering the mouse moves the player's paddle towards the mouse.
+    async fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+        if !matches!(
+            mouse.kind,
+            MouseEventKind::Moved | MouseEventKind::Drag(_) | MouseEventKind::Down(_)
+        ) {
+            return Ok(());
+        }
+        let Some((size, cell_aspect)) = self
+            .clients
+            .lock()
+            .await
+            .get(&self.client_id)
+            .map(|client| Ok::<_, io::Error>((client.terminal.size()?, client.cell_aspect())))
+            .transpose()?
+        else {
+            return Ok(());
+        };
+        let area = Rect::from((Position::ORIGIN, size));
+        if let Some(game) = self.rooms.lock().await.game_mut(self.client_id) {
+            game.move_to_position(
+                self.client_id,
+                Position::new(mouse.column, mouse.row),
+                area,
+                cell_aspect,
+            );
+        }
+        Ok(())
+    }
 }
 
 impl Handler for AppHandler {
     type Error = color_eyre::Report;
 
-    #[instrument(skip(self, _public_key), err)]
+    #[instrument(skip(self, public_key), err)]
     async fn auth_publickey(
         &mut self,
-        _user: &str,
-        _public_key: &PublicKey,
+        user: &str,
+        public_key: &PublicKey,
     ) -> Result<Auth, Self::Error> {
         info!(client_id = ?self.client_id, "Authenticating client");
+        let identity = public_key.fingerprint(HashAlg::Sha256).to_string();
+        self.profile = Some(Profile::load(&identity, user));
         Ok(Auth::Accept)
     }
 
@@ -151,11 +479,59 @@ impl Handler for AppHandler {
         _session: &mut Session,
     ) -> Result<bool, Self::Error> {
         info!(client_id = ?self.client_id, "Opening session");
-        let mut game = self.game.lock().await;
-        game.connect_player(self.client_id)?;
         Ok(true)
     }
 
+    /// The client asked for a shell, joining the game with the default match options.
+    #[instrument(skip(self, session), err)]
+    async fn shell_request(
+        &mut self,
+        channel_id: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.join(MatchConfig::default(), Vec::new()).await;
+        session.channel_success(channel_id)?;
+        Ok(())
+    }
+
+    /// The client ran a command (e.g. `ssh -t pong.example four-player`), which chooses the match
+    /// options, or organizes or enters a tournament.
+    ///
+    /// Options that can't be parsed are reported to the client and the defaults are used instead.
+    #[instrument(skip(self, data, session), err)]
+    async fn exec_request(
+        &mut self,
+        channel_id: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let command = String::from_utf8_lossy(data);
+        let mut notifications = Vec::new();
+        match TournamentRequest::parse(&command, &self.levels) {
+            Ok(Some(request)) => {
+                self.join_tournament(request).await;
+                session.channel_success(channel_id)?;
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(err) => {
+                warn!(client_id = ?self.client_id, %command, "Invalid tournament options: {err}");
+                notifications.push(err.to_string());
+                self.join(MatchConfig::default(), notifications).await;
+                session.channel_success(channel_id)?;
+                return Ok(());
+            }
+        }
+        let config = MatchConfig::parse(&command, &self.levels).unwrap_or_else(|err| {
+            warn!(client_id = ?self.client_id, %command, "Invalid match options: {err}");
+            notifications.push(err.to_string());
+            MatchConfig::default()
+        });
+        self.join(config, notifications).await;
+        session.channel_success(channel_id)?;
+        Ok(())
+    }
+
     #[instrument(skip(self, _session), err)]
     async fn channel_close(
         &mut self,
@@ -163,8 +539,8 @@ impl Handler for AppHandler {
         _session: &mut Session,
     ) -> Result<(), Self::Error> {
         info!(client_id = ?self.client_id, "Closing session");
-        self.game.lock().await.disconnect_player(self.client_id);
-        self.terminals.lock().await.remove(&self.client_id);
+        self.rooms.lock().await.leave(self.client_id);
+        self.clients.lock().await.remove(&self.client_id);
         Ok(())
     }
 
@@ -174,18 +550,46 @@ impl Handler for AppHandler {
         data: &[u8],
         session: &mut Session,
     ) -> Result<(), Self::Error> {
-        match data {
-            // Pressing 'q' closes the connection.
-            b"q" => {
-                let _ = session.close(channel_id);
+        let mut input = self.input.lock().await;
+        let events = input.decode(data, Instant::now());
+        if input.has_pending_escape() {
+            self.flush_escape_later(channel_id, session.handle());
+        }
+        drop(input);
+        for event in events {
+            match event {
+                InputEvent::KeyboardEnhancementFlags(flags) => {
+                    self.keys.release_events =
+                        flags.contains(KeyboardEnhancementFlags::REPORT_EVENT_TYPES);
+                    debug!(client_id = ?self.client_id, ?flags, "Keyboard enhancement enabled");
+                }
+                InputEvent::TerminalVersion(version) => {
+                    debug!(client_id = ?self.client_id, version, "Terminal version");
+                    if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+                        client.capabilities.apply_terminal_version(&version);
+                    }
+                }
+                InputEvent::KittyGraphics(supported) => {
+                    debug!(client_id = ?self.client_id, supported, "Kitty graphics");
+                    if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+                        client.capabilities.apply_kitty_graphics(supported);
+                    }
+                }
+                InputEvent::DeviceAttributes(attributes) => {
+                    debug!(client_id = ?self.client_id, ?attributes, "Device attributes");
+                    if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+                        client.capabilities.apply_device_attributes(&attributes);
+                    }
+                }
+                InputEvent::StatusReport => self.record_latency().await,
+                InputEvent::Key(key) => {
+                    if self.keys.handle(key).await? {
+                        let _ = session.close(channel_id);
+                    }
+                }
+                InputEvent::Mouse(mouse) => self.handle_mouse(mouse).await?,
             }
-            // Pressing 'c' resets the counter for the app.
-            // Every client sees the counter reset.
-            b"w" => self.game.lock().await.move_up(self.client_id),
-            b"s" => self.game.lock().await.move_down(self.client_id),
-            _ => {}
         }
-
         Ok(())
     }
 
@@ -202,7 +606,7 @@ impl Handler for AppHandler {
         session: &mut Session,
     ) -> Result<(), Self::Error> {
         info!(client_id = ?self.client_id, "Creating terminal");
-        let terminal = Terminal::new(SshBackend::new(
+        let mut terminal = Terminal::new(SshBackend::new(
             channel_id,
             session.handle(),
             col_width,
@@ -210,12 +614,41 @@ impl Handler for AppHandler {
             pix_width,
             pix_height,
         ))?;
-        let mut terminals = self.terminals.lock().await;
-        terminals.insert(self.client_id, terminal);
+        terminal.backend_mut().init_session()?;
+        let profile = self.profile.take().unwrap_or_default();
+        let capabilities = Capabilities::from_term(term);
+        debug!(client_id = ?self.client_id, ?capabilities, "Detected terminal capabilities");
+        let client = Client::new(
+            self.client_id,
+            terminal,
+            profile,
+            capabilities,
+            self.themes.clone(),
+        );
+        self.clients.lock().await.insert(self.client_id, client);
 
         Ok(())
     }
 
+    /// The client sent an environment variable (e.g. with `ssh -o SetEnv=COLORTERM=truecolor`).
+    ///
+    /// Only variables that describe the terminal are used. OpenSSH sends these after the pty
+    /// request, so the client's terminal already exists.
+    #[instrument(skip(self, session), err)]
+    async fn env_request(
+        &mut self,
+        channel_id: ChannelId,
+        variable_name: &str,
+        variable_value: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+            client.capabilities.apply_env(variable_name, variable_value);
+        }
+        session.channel_success(channel_id)?;
+        Ok(())
+    }
+
     /// The client's pseudo-terminal window size has changed.
     #[instrument(skip(self, session), err)]
     async fn window_change_request(
@@ -236,8 +669,9 @@ impl Handler for AppHandler {
             pix_width,
             pix_height,
         ))?;
-        let mut terminals = self.terminals.lock().await;
-        terminals.insert(self.client_id, terminal);
+        if let Some(client) = self.clients.lock().await.get_mut(&self.client_id) {
+            client.terminal = terminal;
+        }
 
         Ok(())
     }
@@ -251,11 +685,11 @@ pub mod tests {
 
     #[tokio::test]
     async fn test_auth() {
-        let key = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
         let public_key = key.public_key();
         let addr = None;
         let mut handler = AppServer::new().unwrap().new_client(addr);
-        let result = handler.auth_publickey("test", &public_key);
+        let result = handler.auth_publickey("test", public_key);
         assert_eq!(result.await.unwrap(), Auth::Accept);
     }
 }
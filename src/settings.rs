@@ -0,0 +1,327 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Clear},
+};
+
+use crate::{
+    ball::BallGlyphs,
+    capabilities::Capabilities,
+    keymap::{key_name, Action},
+    profile::Profile,
+    theme::Themes,
+    viewer::Preferences,
+};
+
+/// An in-game screen where a player can rebind their keys and change their preferences.
+///
+/// The screen is drawn over the game for the player who opened it. While it is open, all of the
+/// player's input goes to the screen rather than the game.
+#[derive(Debug, Default)]
+pub struct SettingsScreen {
+    selected: usize,
+    // Whether the next key pressed will be bound to the selected action
+    capturing: bool,
+}
+
+/// A line on the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Binding(Action),
+    Toggle(Toggle),
+    OwnColor,
+    BallGlyphs,
+    Theme,
+}
+
+/// A preference that is either on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Toggle {
+    Mirror,
+    CourtMarkings,
+    Graphics,
+    Trail,
+    HitFlash,
+    ScoreShake,
+    Muted,
+    BellPaddleHit,
+    BellWallBounce,
+    BellPoint,
+    BellMatch,
+    DesktopNotifications,
+}
+
+impl Toggle {
+    fn label(self) -> &'static str {
+        match self {
+            Toggle::Mirror => "Always play on left",
+            Toggle::CourtMarkings => "Court markings",
+            Toggle::Graphics => "Graphics",
+            Toggle::Trail => "Ball trail",
+            Toggle::HitFlash => "Paddle hit flash",
+            Toggle::ScoreShake => "Shake on score",
+            Toggle::Muted => "Mute sounds",
+            Toggle::BellPaddleHit => "Bell on paddle hit",
+            Toggle::BellWallBounce => "Bell on wall bounce",
+            Toggle::BellPoint => "Bell on point",
+            Toggle::BellMatch => "Bell on match won",
+            Toggle::DesktopNotifications => "Desktop notifications",
+        }
+    }
+
+    fn value(self, preferences: &Preferences) -> bool {
+        match self {
+            Toggle::Mirror => preferences.mirror,
+            Toggle::CourtMarkings => preferences.court_markings,
+            Toggle::Graphics => preferences.graphics,
+            Toggle::Trail => preferences.trail,
+            Toggle::HitFlash => preferences.hit_flash,
+            Toggle::ScoreShake => preferences.score_shake,
+            Toggle::Muted => preferences.muted,
+            Toggle::BellPaddleHit => preferences.bell_paddle_hit,
+            Toggle::BellWallBounce => preferences.bell_wall_bounce,
+            Toggle::BellPoint => preferences.bell_point,
+            Toggle::BellMatch => preferences.bell_match,
+            Toggle::DesktopNotifications => preferences.desktop_notifications,
+        }
+    }
+
+    fn value_mut(self, preferences: &mut Preferences) -> &mut bool {
+        match self {
+            Toggle::Mirror => &mut preferences.mirror,
+            Toggle::CourtMarkings => &mut preferences.court_markings,
+            Toggle::Graphics => &mut preferences.graphics,
+            Toggle::Trail => &mut preferences.trail,
+            Toggle::HitFlash => &mut preferences.hit_flash,
+            Toggle::ScoreShake => &mut preferences.score_shake,
+            Toggle::Muted => &mut preferences.muted,
+            Toggle::BellPaddleHit => &mut preferences.bell_paddle_hit,
+            Toggle::BellWallBounce => &mut preferences.bell_wall_bounce,
+            Toggle::BellPoint => &mut preferences.bell_point,
+            Toggle::BellMatch => &mut preferences.bell_match,
+            Toggle::DesktopNotifications => &mut preferences.desktop_notifications,
+        }
+    }
+}
+
+impl Row {
+    fn all() -> Vec<Row> {
+        let mut rows: Vec<_> = Action::ALL.into_iter().map(Row::Binding).collect();
+        rows.extend([
+            Row::Toggle(Toggle::Mirror),
+            Row::OwnColor,
+            Row::BallGlyphs,
+            Row::Theme,
+            Row::Toggle(Toggle::CourtMarkings),
+            Row::Toggle(Toggle::Graphics),
+            Row::Toggle(Toggle::Trail),
+            Row::Toggle(Toggle::HitFlash),
+            Row::Toggle(Toggle::ScoreShake),
+            Row::Toggle(Toggle::Muted),
+            Row::Toggle(Toggle::BellPaddleHit),
+            Row::Toggle(Toggle::BellWallBounce),
+            Row::Toggle(Toggle::BellPoint),
+            Row::Toggle(Toggle::BellMatch),
+            Row::Toggle(Toggle::DesktopNotifications),
+        ]);
+        rows
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Row::Binding(action) => action.description(),
+            Row::Toggle(toggle) => toggle.label(),
+            Row::OwnColor => "Your color",
+            Row::BallGlyphs => "Ball glyphs",
+            Row::Theme => "Theme",
+        }
+    }
+
+    fn value(self, profile: &Profile) -> Span<'static> {
+        match self {
+            Row::Binding(action) => {
+                let keys: Vec<_> = profile
+                    .keymap
+                    .keys(action)
+                    .iter()
+                    .copied()
+                    .map(key_name)
+                    .collect();
+                Span::raw(keys.join(" "))
+            }
+            Row::Toggle(toggle) => Span::raw(if toggle.value(&profile.preferences) {
+                "on"
+            } else {
+                "off"
+            }),
+            Row::OwnColor => {
+                let color = profile.preferences.own_color;
+                Span::styled(color.to_string(), color)
+            }
+            Row::BallGlyphs => Span::raw(profile.preferences.ball_glyphs.name()),
+            Row::Theme => Span::raw(profile.preferences.theme.clone()),
+        }
+    }
+}
+
+/// What should happen after the settings screen handles a key.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SettingsOutcome {
+    Continue,
+    Close,
+}
+
+impl SettingsScreen {
+    pub fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        profile: &mut Profile,
+        themes: &Themes,
+    ) -> SettingsOutcome {
+        if key.kind == KeyEventKind::Release {
+            return SettingsOutcome::Continue;
+        }
+        let rows = Row::all();
+        let row = rows[self.selected];
+        if self.capturing {
+            self.capturing = false;
+            if let (Row::Binding(action), false) = (row, key.code == KeyCode::Esc) {
+                profile.keymap.bind(action, &[key.code]);
+            }
+            return SettingsOutcome::Continue;
+        }
+        match key.code {
+            KeyCode::Esc => return SettingsOutcome::Close,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(rows.len() - 1),
+            KeyCode::Enter => self.activate(row, profile, themes),
+            KeyCode::Char('r') => Self::reset(row, profile),
+            _ => {}
+        }
+        SettingsOutcome::Continue
+    }
+
+    fn activate(&mut self, row: Row, profile: &mut Profile, themes: &Themes) {
+        let preferences = &mut profile.preferences;
+        match row {
+            Row::Binding(_) => self.capturing = true,
+            Row::Toggle(toggle) => {
+                let value = toggle.value_mut(preferences);
+                *value = !*value;
+            }
+            Row::OwnColor => {
+                let colors = Preferences::OWN_COLORS;
+                let index = colors
+                    .iter()
+                    .position(|&color| color == preferences.own_color)
+                    .map_or(0, |index| (index + 1) % colors.len());
+                preferences.own_color = colors[index];
+            }
+            Row::BallGlyphs => {
+                let all = BallGlyphs::ALL;
+                let index = all
+                    .iter()
+                    .position(|&glyphs| glyphs == preferences.ball_glyphs)
+                    .map_or(0, |index| (index + 1) % all.len());
+                preferences.ball_glyphs = all[index];
+            }
+            Row::Theme => preferences.theme = themes.next(&preferences.theme).to_string(),
+        }
+    }
+
+    fn reset(row: Row, profile: &mut Profile) {
+        let defaults = Preferences::default();
+        match row {
+            Row::Binding(action) => {
+                // the keys stay as they are if that would leave another action without keys
+                profile.keymap.reset(action);
+            }
+            Row::Toggle(toggle) => {
+                *toggle.value_mut(&mut profile.preferences) = toggle.value(&defaults);
+            }
+            Row::OwnColor => profile.preferences.own_color = defaults.own_color,
+            Row::BallGlyphs => profile.preferences.ball_glyphs = defaults.ball_glyphs,
+            Row::Theme => profile.preferences.theme = defaults.theme,
+        }
+    }
+
+    /// The part of the given area the settings screen is drawn over.
+    pub fn area(area: Rect) -> Rect {
+        let height = Row::all().len() as u16 + 4;
+        let [area] = Layout::horizontal([Constraint::Length(52)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        area
+    }
+
+    pub fn widget<'a>(
+        &'a self,
+        profile: &'a Profile,
+        capabilities: Capabilities,
+    ) -> impl Widget + 'a {
+        SettingsWidget {
+            screen: self,
+            profile,
+            capabilities,
+        }
+    }
+}
+
+struct SettingsWidget<'a> {
+    screen: &'a SettingsScreen,
+    profile: &'a Profile,
+    capabilities: Capabilities,
+}
+
+impl Widget for SettingsWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows = Row::all();
+        let area = SettingsScreen::area(area);
+        Clear.render(area, buf);
+        let capabilities = self.capabilities;
+        let block = Block::bordered()
+            .border_set(capabilities.border_set())
+            .title("Settings")
+            .title_alignment(Alignment::Center)
+            .style((
+                capabilities.color(Color::White),
+                capabilities.color(Color::Black),
+            ));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        // scroll so the selected row stays visible on short terminals, leaving room for the help
+        let visible = usize::from(inner.height.saturating_sub(2)).max(1);
+        let offset = (self.screen.selected + 1).saturating_sub(visible);
+        let lines = rows.iter().enumerate().skip(offset).take(visible);
+        for ((index, row), line_area) in lines.zip(inner.rows()) {
+            let value = if self.screen.capturing && index == self.screen.selected {
+                Span::raw(capabilities.text("press a key…", "press a key..."))
+            } else {
+                row.value(self.profile)
+            };
+            let line = Line::from(vec![Span::raw(format!(" {:<22} ", row.label())), value]);
+            let line = if index == self.screen.selected {
+                line.reversed()
+            } else {
+                line
+            };
+            line.render(line_area, buf);
+        }
+
+        let help = if self.screen.capturing {
+            "Esc cancel"
+        } else {
+            capabilities.text(
+                "↑↓ select  Enter change  r reset  Esc close",
+                "Up/Down select  Enter change  r reset  Esc close",
+            )
+        };
+        let last_row = inner.rows().next_back().unwrap_or_default();
+        Line::from(help).dim().centered().render(last_row, buf);
+    }
+}
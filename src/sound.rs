@@ -0,0 +1,120 @@
+use crate::{event::GameEvent, viewer::Preferences};
+
+/// The terminal bell, which most terminals play as a short sound or show as a visual bell.
+pub const BELL: &str = "\x07";
+
+/// Whether the player wants the bell rung for the given event.
+pub fn rings_bell(event: &GameEvent, preferences: &Preferences) -> bool {
+    if preferences.muted {
+        return false;
+    }
+    match event {
+        GameEvent::PaddleHit { .. } => preferences.bell_paddle_hit,
+        GameEvent::WallBounce => preferences.bell_wall_bounce,
+        GameEvent::PointScored { .. } | GameEvent::LifeLost { .. } | GameEvent::RunEnded { .. } => {
+            preferences.bell_point
+        }
+        GameEvent::MatchEnded { .. } => preferences.bell_match,
+        GameEvent::Serve
+        | GameEvent::PowerUpCollected { .. }
+        | GameEvent::PowerUpExpired { .. }
+        | GameEvent::PlayerEliminated { .. }
+        | GameEvent::PlayerJoined { .. }
+        | GameEvent::PlayerLeft { .. } => false,
+    }
+}
+
+/// A desktop notification, using the OSC 9 sequence supported by iTerm2, WezTerm, kitty, Ghostty
+/// and Windows Terminal. Other terminals ignore it.
+pub fn notification(message: &str) -> String {
+    // control characters would end the sequence early
+    let message: String = message.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]9;{message}\x1b\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> [GameEvent; 4] {
+        [
+            GameEvent::PaddleHit { slot: 0 },
+            GameEvent::WallBounce,
+            GameEvent::PointScored {
+                team: 0,
+                score: vec![1, 0],
+            },
+            GameEvent::MatchEnded {
+                winner: 0,
+                score: vec![11, 0],
+            },
+        ]
+    }
+
+    #[test]
+    fn one_event_per_preference() {
+        let none = Preferences {
+            bell_paddle_hit: false,
+            bell_wall_bounce: false,
+            bell_point: false,
+            bell_match: false,
+            ..Preferences::default()
+        };
+        let only = [
+            Preferences {
+                bell_paddle_hit: true,
+                ..none.clone()
+            },
+            Preferences {
+                bell_wall_bounce: true,
+                ..none.clone()
+            },
+            Preferences {
+                bell_point: true,
+                ..none.clone()
+            },
+            Preferences {
+                bell_match: true,
+                ..none.clone()
+            },
+        ];
+        for (i, preferences) in only.iter().enumerate() {
+            let rung: Vec<bool> = events()
+                .iter()
+                .map(|event| rings_bell(event, preferences))
+                .collect();
+            let expected: Vec<bool> = (0..only.len()).map(|j| j == i).collect();
+            assert_eq!(rung, expected);
+        }
+        assert!(!rings_bell(&GameEvent::Serve, &Preferences::default()));
+        assert!(rings_bell(
+            &GameEvent::LifeLost { team: 0, lives: 2 },
+            &Preferences::default()
+        ));
+    }
+
+    #[test]
+    fn mute_overrides_everything() {
+        let preferences = Preferences {
+            muted: true,
+            bell_paddle_hit: true,
+            bell_wall_bounce: true,
+            bell_point: true,
+            bell_match: true,
+            ..Preferences::default()
+        };
+        for event in events() {
+            assert!(!rings_bell(&event, &preferences), "{event:?}");
+        }
+    }
+
+    #[test]
+    fn notification_cannot_end_early() {
+        assert_eq!(notification("Your turn"), "\x1b]9;Your turn\x1b\\");
+        // a name with its own escape sequence or bell stays inside the notification
+        assert_eq!(
+            notification("eve\x1b\\\x1b]0;pwned\x07\n"),
+            "\x1b]9;eve\\]0;pwned\x1b\\"
+        );
+    }
+}
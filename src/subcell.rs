@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use ratatui::prelude::*;
+
+/// A way of dividing each terminal cell into a grid of smaller "pixels".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// 2x2 pixels per cell using quadrant block elements (`▘▝▖▗`), which almost all fonts support.
+    Quadrants,
+    /// 2x3 pixels per cell using sextants from Symbols for Legacy Computing (`🬀🬁🬂`).
+    Sextants,
+    /// 2x4 pixels per cell using braille patterns (`⠁⠈⡀⢀`).
+    Braille,
+}
+
+impl Resolution {
+    /// The number of pixels across and down each cell.
+    pub const fn cell_size(self) -> (u16, u16) {
+        match self {
+            Resolution::Quadrants => (2, 2),
+            Resolution::Sextants => (2, 3),
+            Resolution::Braille => (2, 4),
+        }
+    }
+
+    /// The glyph for a cell with the given pixels set.
+    ///
+    /// Bit `row * 2 + column` of the mask is set for each pixel that is on.
+    pub fn glyph(self, mask: u8) -> char {
+        match self {
+            Resolution::Quadrants => {
+                const QUADRANTS: [char; 16] = [
+                    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+                ];
+                QUADRANTS[(mask & 0xf) as usize]
+            }
+            Resolution::Sextants => match mask & 0x3f {
+                0 => ' ',
+                // the sextants block skips the two half blocks and the full block, which already
+                // exist in the block elements block
+                0b010101 => '▌',
+                0b101010 => '▐',
+                0b111111 => '█',
+                mask => {
+                    let skipped = u32::from(mask > 0b010101) + u32::from(mask > 0b101010);
+                    char::from_u32(0x1fb00 + u32::from(mask) - 1 - skipped).unwrap_or('█')
+                }
+            },
+            Resolution::Braille => {
+                // braille dots are numbered down the left column and then the right column, with
+                // the bottom row added later as dots 7 and 8
+                const DOTS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+                let bits = (0..8)
+                    .filter(|bit| mask & (1 << bit) != 0)
+                    .fold(0, |bits, bit| bits | DOTS[bit]);
+                char::from_u32(0x2800 + u32::from(bits)).unwrap_or('⣿')
+            }
+        }
+    }
+}
+
+/// A sparse set of pixels drawn over an area at sub-cell resolution.
+///
+/// Pixels are collected with [`SubCells::fill`] and then drawn into a buffer, combining all of the
+/// pixels in each cell into a single glyph.
+#[derive(Debug)]
+pub struct SubCells {
+    resolution: Resolution,
+    area: Rect,
+    // the pixel mask of each cell that has any pixels set, keyed by (row, column)
+    cells: BTreeMap<(u16, u16), u8>,
+}
+
+impl SubCells {
+    pub fn new(resolution: Resolution, area: Rect) -> Self {
+        Self {
+            resolution,
+            area,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    /// The size of the area in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = self.resolution.cell_size();
+        (
+            u32::from(self.area.width) * u32::from(width),
+            u32::from(self.area.height) * u32::from(height),
+        )
+    }
+
+    /// Turn on a rectangle of pixels. Pixels outside the area are ignored.
+    pub fn fill(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let (cell_width, cell_height) = self.resolution.cell_size();
+        let (cell_width, cell_height) = (u32::from(cell_width), u32::from(cell_height));
+        let (max_x, max_y) = self.size();
+        for py in y..(y + height).min(max_y) {
+            for px in x..(x + width).min(max_x) {
+                let cell = ((py / cell_height) as u16, (px / cell_width) as u16);
+                let bit = (py % cell_height) * cell_width + px % cell_width;
+                *self.cells.entry(cell).or_default() |= 1 << bit;
+            }
+        }
+    }
+
+    pub fn render(&self, buf: &mut Buffer, style: Style) {
+        for (&(row, column), &mask) in &self.cells {
+            let position = Position::new(self.area.x + column, self.area.y + row);
+            if let Some(cell) = buf.cell_mut(position) {
+                cell.set_char(self.resolution.glyph(mask)).set_style(style);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs() {
+        assert_eq!(Resolution::Quadrants.glyph(0b1001), '▚');
+        assert_eq!(Resolution::Sextants.glyph(0b000001), '🬀');
+        assert_eq!(Resolution::Sextants.glyph(0b010101), '▌');
+        assert_eq!(Resolution::Sextants.glyph(0b111110), '🬻');
+        assert_eq!(Resolution::Braille.glyph(0b0000_0011), '⠉');
+        assert_eq!(Resolution::Braille.glyph(0b1100_0000), '⣀');
+    }
+}
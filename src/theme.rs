@@ -0,0 +1,200 @@
+use std::fs;
+
+use color_eyre::Result;
+use ratatui::style::Color;
+use tracing::{info, warn};
+
+use crate::profile;
+
+/// The colors used to draw the court.
+///
+/// Colors are converted to what each client's terminal can display when drawing, so themes can
+/// use RGB colors freely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    /// The border and text around the court.
+    pub foreground: Color,
+    pub score: Color,
+    pub ball: Color,
+    pub paddle: Color,
+}
+
+impl Theme {
+    /// The default theme: light on dark gray, with no colors other than each player's own.
+    pub fn classic() -> Self {
+        Self {
+            name: "classic".to_string(),
+            background: Color::DarkGray,
+            foreground: Color::White,
+            score: Color::White,
+            ball: Color::White,
+            paddle: Color::White,
+        }
+    }
+
+    /// Bright white on black, with a yellow ball that stands out from the paddles.
+    pub fn high_contrast() -> Self {
+        Self {
+            name: "high-contrast".to_string(),
+            background: Color::Black,
+            foreground: Color::White,
+            score: Color::White,
+            ball: Color::LightYellow,
+            paddle: Color::White,
+        }
+    }
+
+    /// Blue paddles and an orange ball from the Okabe-Ito palette, which stay distinguishable with
+    /// the common forms of color blindness.
+    pub fn colorblind() -> Self {
+        Self {
+            name: "colorblind".to_string(),
+            background: Color::Black,
+            foreground: Color::Rgb(240, 240, 240),
+            score: Color::Rgb(240, 228, 66),
+            ball: Color::Rgb(230, 159, 0),
+            paddle: Color::Rgb(86, 180, 233),
+        }
+    }
+
+    /// Green on black, like an old phosphor monitor.
+    pub fn phosphor() -> Self {
+        Self {
+            name: "phosphor".to_string(),
+            background: Color::Rgb(0, 16, 0),
+            foreground: Color::Rgb(32, 160, 32),
+            score: Color::Rgb(51, 255, 51),
+            ball: Color::Rgb(51, 255, 51),
+            paddle: Color::Rgb(51, 255, 51),
+        }
+    }
+
+    /// Parse a theme file.
+    ///
+    /// Theme files use the same `key = value` lines as profiles, with colors in any format
+    /// ratatui can parse (e.g. `red`, `#ff8800` or `208`). Missing colors are taken from the
+    /// classic theme.
+    fn parse(name: &str, contents: &str) -> Self {
+        let mut theme = Self {
+            name: name.to_string(),
+            ..Self::classic()
+        };
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let color = match key {
+                "background" => &mut theme.background,
+                "foreground" => &mut theme.foreground,
+                "score" => &mut theme.score,
+                "ball" => &mut theme.ball,
+                "paddle" => &mut theme.paddle,
+                _ => continue,
+            };
+            match value.parse() {
+                Ok(value) => *color = value,
+                Err(_) => warn!("Invalid color for {key} in theme {name}: {value}"),
+            }
+        }
+        theme
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+/// The themes players can choose from: the built-in themes followed by any loaded from files.
+#[derive(Debug, Clone)]
+pub struct Themes {
+    themes: Vec<Theme>,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self {
+            themes: vec![
+                Theme::classic(),
+                Theme::high_contrast(),
+                Theme::colorblind(),
+                Theme::phosphor(),
+            ],
+        }
+    }
+}
+
+impl Themes {
+    /// Load the built-in themes and any `*.theme` files in the `themes` config directory.
+    ///
+    /// A file with the same name as a built-in theme replaces it. Unreadable files are logged and
+    /// skipped.
+    pub fn load() -> Result<Self> {
+        let mut themes = Self::default();
+        let dir = profile::config_dir()?.join("themes");
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(themes);
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let Some(name) = path
+                .file_stem()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "theme"))
+                .and_then(|name| name.to_str())
+            else {
+                continue;
+            };
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    info!("Loading theme from {}", path.display());
+                    themes.insert(Theme::parse(name, &contents));
+                }
+                Err(err) => warn!("Failed to read theme from {}: {err}", path.display()),
+            }
+        }
+        Ok(themes)
+    }
+
+    fn insert(&mut self, theme: Theme) {
+        match self.themes.iter_mut().find(|t| t.name == theme.name) {
+            Some(existing) => *existing = theme,
+            None => self.themes.push(theme),
+        }
+    }
+
+    /// The theme with the given name, or the first theme if there is none.
+    pub fn get(&self, name: &str) -> &Theme {
+        self.themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .unwrap_or(&self.themes[0])
+    }
+
+    /// The name of the theme after the given one, wrapping around to the first.
+    pub fn next(&self, name: &str) -> &str {
+        let index = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == name)
+            .map_or(0, |index| (index + 1) % self.themes.len());
+        &self.themes[index].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_theme_file() {
+        let theme = Theme::parse("sunset", "ball = #ff8800\npaddle = magenta\nscore = nope\n");
+        assert_eq!(theme.name, "sunset");
+        assert_eq!(theme.ball, Color::Rgb(255, 136, 0));
+        assert_eq!(theme.paddle, Color::Magenta);
+        assert_eq!(theme.score, Theme::classic().score);
+        assert_eq!(theme.background, Theme::classic().background);
+    }
+}
@@ -0,0 +1,786 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear},
+};
+
+use crate::{
+    level::Levels,
+    mode::{MatchConfig, Mode, ParseError},
+    viewer::Viewer,
+};
+
+/// How many matches a player can lose before they are out of a tournament.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Elimination {
+    /// Players are out after losing once.
+    #[default]
+    Single,
+    /// Players who lose once drop into a losers bracket, and are out after losing twice. The
+    /// winners of the two brackets meet in a grand final, which is played again if the winner of
+    /// the losers bracket wins it, as both players have then lost once.
+    Double,
+}
+
+impl Elimination {
+    pub const ALL: [Elimination; 2] = [Elimination::Single, Elimination::Double];
+
+    /// The name used to choose the elimination when creating a tournament.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Elimination::Single => "single-elimination",
+            Elimination::Double => "double-elimination",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|elimination| elimination.name() == name)
+    }
+}
+
+/// Where a player in a bracket match comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// The entrant with the given seed, which is a bye if there are fewer entrants.
+    Seed(usize),
+    /// The winner of the match at the given index.
+    Winner(usize),
+    /// The loser of the match at the given index.
+    Loser(usize),
+    /// The loser of the grand final at the given index if it was their first loss, or a bye if
+    /// they came from the losers bracket and are out.
+    Reset(usize),
+}
+
+/// Who takes one of the places in a bracket match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// Decided by a match that hasn't been played yet.
+    Pending,
+    /// The entrant with the given seed.
+    Entrant(usize),
+    /// Nobody, so the other player goes through without playing.
+    Bye,
+}
+
+/// The part of a bracket a match is played in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Winners,
+    Losers,
+    GrandFinal,
+}
+
+impl Section {
+    pub const ALL: [Section; 3] = [Section::Winners, Section::Losers, Section::GrandFinal];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Section::Winners => "Winners bracket",
+            Section::Losers => "Losers bracket",
+            Section::GrandFinal => "Grand final",
+        }
+    }
+}
+
+/// How a bracket match ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchResult {
+    /// The place of the winner in the match, 0 or 1.
+    pub winner: usize,
+    /// The score in each place, or `None` if the match wasn't played out, such as for a bye or
+    /// a forfeit.
+    pub score: Option<[u32; 2]>,
+}
+
+/// A match in a tournament bracket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketMatch {
+    pub section: Section,
+    /// The round within the section, from 0.
+    pub round: usize,
+    sources: [Source; 2],
+    pub result: Option<MatchResult>,
+    /// Whether the match is being played now.
+    pub playing: bool,
+}
+
+/// The matches of a tournament, and who plays in each as results come in.
+///
+/// Brackets are sized for the next power of two entrants, and the top seeds get byes in place of
+/// the missing entrants. Each match only depends on earlier ones, so the matches are in an order
+/// they can be played in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bracket {
+    pub elimination: Elimination,
+    entrants: usize,
+    pub matches: Vec<BracketMatch>,
+}
+
+impl Bracket {
+    pub fn new(elimination: Elimination, entrants: usize) -> Self {
+        let mut bracket = Self {
+            elimination,
+            entrants,
+            matches: Vec::new(),
+        };
+        let size = entrants.next_power_of_two().max(2);
+        let mut winners: Vec<_> = seed_order(size).into_iter().map(Source::Seed).collect();
+        let mut dropped = Vec::new();
+        let mut round = 0;
+        while winners.len() > 1 {
+            let ids = bracket.add_round(Section::Winners, round, &winners);
+            dropped.push(ids.iter().map(|&id| Source::Loser(id)).collect::<Vec<_>>());
+            winners = winners_of(ids);
+            round += 1;
+        }
+        if elimination == Elimination::Double {
+            let mut dropped = dropped.into_iter();
+            let mut losers = dropped.next().unwrap_or_default();
+            let mut round = 0;
+            if losers.len() > 1 {
+                losers = winners_of(bracket.add_round(Section::Losers, round, &losers));
+                round += 1;
+            }
+            for mut next in dropped {
+                // reversed, so players who met in the winners bracket don't meet again straight
+                // away
+                next.reverse();
+                let pairs: Vec<_> = losers
+                    .into_iter()
+                    .zip(next)
+                    .flat_map(<[_; 2]>::from)
+                    .collect();
+                losers = winners_of(bracket.add_round(Section::Losers, round, &pairs));
+                round += 1;
+                if losers.len() > 1 {
+                    losers = winners_of(bracket.add_round(Section::Losers, round, &losers));
+                    round += 1;
+                }
+            }
+            let ids = bracket.add_round(Section::GrandFinal, 0, &[winners[0], losers[0]]);
+            bracket.add_round(
+                Section::GrandFinal,
+                1,
+                &[Source::Winner(ids[0]), Source::Reset(ids[0])],
+            );
+        }
+        bracket.advance();
+        bracket
+    }
+
+    /// Add a match for each pair of the given sources, returning their indices.
+    fn add_round(&mut self, section: Section, round: usize, sources: &[Source]) -> Vec<usize> {
+        sources
+            .chunks(2)
+            .map(|pair| {
+                self.matches.push(BracketMatch {
+                    section,
+                    round,
+                    sources: [pair[0], pair[1]],
+                    result: None,
+                    playing: false,
+                });
+                self.matches.len() - 1
+            })
+            .collect()
+    }
+
+    fn entry(&self, source: Source) -> Entry {
+        let (index, winner) = match source {
+            Source::Seed(seed) if seed < self.entrants => return Entry::Entrant(seed),
+            Source::Seed(_) => return Entry::Bye,
+            Source::Winner(index) => (index, true),
+            Source::Loser(index) => (index, false),
+            // the player from the winners bracket is in the first place
+            Source::Reset(index) => {
+                return match self.matches[index].result {
+                    Some(result) if result.winner == 1 => {
+                        self.entry(self.matches[index].sources[0])
+                    }
+                    Some(_) => Entry::Bye,
+                    None => Entry::Pending,
+                };
+            }
+        };
+        let bracket_match = &self.matches[index];
+        match bracket_match.result {
+            Some(result) => {
+                let place = if winner {
+                    result.winner
+                } else {
+                    1 - result.winner
+                };
+                self.entry(bracket_match.sources[place])
+            }
+            None => Entry::Pending,
+        }
+    }
+
+    /// Who plays in each place of the match at the given index.
+    pub fn entries(&self, index: usize) -> [Entry; 2] {
+        self.matches[index].sources.map(|source| self.entry(source))
+    }
+
+    /// Put players with a bye straight through to their next match.
+    fn advance(&mut self) {
+        // a single pass is enough, as each match only depends on earlier ones
+        for index in 0..self.matches.len() {
+            if self.matches[index].result.is_some() {
+                continue;
+            }
+            let winner = match self.entries(index) {
+                [Entry::Entrant(_) | Entry::Bye, Entry::Bye] => 0,
+                [Entry::Bye, Entry::Entrant(_)] => 1,
+                _ => continue,
+            };
+            self.matches[index].result = Some(MatchResult {
+                winner,
+                score: None,
+            });
+        }
+    }
+
+    /// The matches that can be played now, with the seeds of the two players.
+    pub fn ready(&self) -> Vec<(usize, [usize; 2])> {
+        (0..self.matches.len())
+            .filter(|&index| {
+                let bracket_match = &self.matches[index];
+                bracket_match.result.is_none() && !bracket_match.playing
+            })
+            .filter_map(|index| match self.entries(index) {
+                [Entry::Entrant(a), Entry::Entrant(b)] => Some((index, [a, b])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Record that the entrant with the given seed won the match at the given index, with the
+    /// score in each place if it was played out.
+    pub fn record(&mut self, index: usize, winner: usize, score: Option<[u32; 2]>) {
+        let entries = self.entries(index);
+        let place = entries
+            .iter()
+            .position(|&entry| entry == Entry::Entrant(winner))
+            .unwrap_or_default();
+        let bracket_match = &mut self.matches[index];
+        bracket_match.result = Some(MatchResult {
+            winner: place,
+            score,
+        });
+        bracket_match.playing = false;
+        self.advance();
+    }
+
+    /// The seed of the entrant who won the tournament, once the last match has been played.
+    pub fn champion(&self) -> Option<usize> {
+        let last = self.matches.len().checked_sub(1)?;
+        let result = self.matches[last].result?;
+        match self.entries(last)[result.winner] {
+            Entry::Entrant(seed) => Some(seed),
+            Entry::Pending | Entry::Bye => None,
+        }
+    }
+}
+
+/// The winners of the matches at the given indices, to play in the next round.
+fn winners_of(indices: Vec<usize>) -> Vec<Source> {
+    indices.into_iter().map(Source::Winner).collect()
+}
+
+/// The seeds in bracket order for a bracket of the given power of two size, so the top seeds
+/// only meet in the later rounds, e.g. `[0, 3, 1, 2]` for four players.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let count = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|&seed| [seed, count - 1 - seed])
+            .collect();
+    }
+    order
+}
+
+/// A player who has entered a tournament.
+#[derive(Debug, Clone)]
+pub struct Entrant {
+    /// The identity of the player's key (see [`Profile`](crate::profile::Profile)), so they keep
+    /// their place if they reconnect.
+    pub identity: String,
+    pub name: String,
+    /// The client the player is connected as, if they are connected.
+    pub client_id: Option<usize>,
+    /// When the player disconnected, if they haven't come back since.
+    pub left: Option<Instant>,
+}
+
+impl Entrant {
+    /// How long a player who disconnected has to come back before they lose their next match by
+    /// walkover.
+    pub const WALKOVER_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Whether the player disconnected and didn't come back in time for their match.
+    pub fn walked_over(&self, now: Instant) -> bool {
+        self.left
+            .is_some_and(|left| now.duration_since(left) >= Self::WALKOVER_TIMEOUT)
+    }
+}
+
+/// A knockout tournament, created by an organizer and entered by players over SSH.
+///
+/// Players are seeded in the order they enter. Each match is played in a room of its own as soon
+/// as both players are connected and not playing another match.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    pub name: String,
+    /// The options every match is played with.
+    pub config: MatchConfig,
+    /// How many players the tournament is for.
+    pub players: usize,
+    pub entrants: Vec<Entrant>,
+    pub bracket: Bracket,
+}
+
+impl Tournament {
+    /// The most players a tournament can be for.
+    pub const MAX_PLAYERS: usize = 16;
+
+    pub fn new(name: &str, elimination: Elimination, players: usize, config: MatchConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            config,
+            players,
+            entrants: Vec::new(),
+            bracket: Bracket::new(elimination, players),
+        }
+    }
+
+    /// Enter the player with the given identity, returning their seed.
+    ///
+    /// A player who has already entered takes their place again as the given client.
+    pub fn enter(
+        &mut self,
+        identity: &str,
+        name: &str,
+        client_id: usize,
+    ) -> Result<usize, TournamentError> {
+        if let Some(seed) = self.seed(identity) {
+            let entrant = &mut self.entrants[seed];
+            entrant.client_id = Some(client_id);
+            entrant.left = None;
+            return Ok(seed);
+        }
+        if self.entrants.len() >= self.players {
+            return Err(TournamentError::Full(self.name.clone()));
+        }
+        self.entrants.push(Entrant {
+            identity: identity.to_string(),
+            name: name.to_string(),
+            client_id: Some(client_id),
+            left: None,
+        });
+        Ok(self.entrants.len() - 1)
+    }
+
+    /// The seed of the player with the given identity, if they have entered.
+    pub fn seed(&self, identity: &str) -> Option<usize> {
+        self.entrants
+            .iter()
+            .position(|entrant| entrant.identity == identity)
+    }
+
+    /// The name of the entrant with the given seed, or a placeholder if nobody has that seed
+    /// yet.
+    pub fn entrant_name(&self, seed: usize) -> String {
+        self.entrants.get(seed).map_or_else(
+            || format!("Player {}", seed + 1),
+            |entrant| entrant.name.clone(),
+        )
+    }
+
+    /// A widget that draws the bracket for the given viewer, highlighting the entrant with the
+    /// given seed.
+    pub fn view<'a>(&'a self, viewer: &'a Viewer<'a>, own: Option<usize>) -> BracketView<'a> {
+        BracketView {
+            tournament: self,
+            viewer,
+            own,
+        }
+    }
+}
+
+/// What a client asked to do with a tournament, given as the SSH command.
+#[derive(Debug, Clone)]
+pub enum TournamentRequest {
+    /// Organize the given tournament.
+    Create(Box<Tournament>),
+    /// Enter the tournament with the given name.
+    Enter(String),
+}
+
+impl TournamentRequest {
+    /// Parse the tournament options in the SSH command, if there are any.
+    ///
+    /// `tournament=NAME` enters the named tournament. Adding `players=N` creates it instead, along
+    /// with `single-elimination` (the default) or `double-elimination` and any of the
+    /// [match options](MatchConfig::parse) to play every match with.
+    pub fn parse(command: &str, levels: &Levels) -> Result<Option<Self>, TournamentError> {
+        let mut name = None;
+        let mut players = None;
+        let mut elimination = Elimination::default();
+        let mut options = Vec::new();
+        for option in command.split_whitespace() {
+            if let Some(value) = option.strip_prefix("tournament=") {
+                name = Some(value);
+            } else if let Some(value) = option.strip_prefix("players=") {
+                let count = value
+                    .parse()
+                    .ok()
+                    .filter(|count| (2..=Tournament::MAX_PLAYERS).contains(count))
+                    .ok_or_else(|| TournamentError::InvalidPlayers(value.to_string()))?;
+                players = Some(count);
+            } else if let Some(value) = Elimination::from_name(option) {
+                elimination = value;
+            } else {
+                options.push(option);
+            }
+        }
+        let Some(name) = name else {
+            return if players.is_some() || elimination != Elimination::default() {
+                Err(TournamentError::MissingName)
+            } else {
+                Ok(None)
+            };
+        };
+        let Some(players) = players else {
+            return if options.is_empty() && elimination == Elimination::default() {
+                Ok(Some(Self::Enter(name.to_string())))
+            } else {
+                Err(TournamentError::MissingPlayers)
+            };
+        };
+        let config = MatchConfig::parse(&options.join(" "), levels)?;
+        if config.mode != Mode::Classic {
+            return Err(TournamentError::NotClassic(config.mode));
+        }
+        Ok(Some(Self::Create(Box::new(Tournament::new(
+            name,
+            elimination,
+            players,
+            config,
+        )))))
+    }
+}
+
+/// A problem creating or entering a tournament.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TournamentError {
+    Options(ParseError),
+    InvalidPlayers(String),
+    MissingName,
+    MissingPlayers,
+    NotClassic(Mode),
+    Exists(String),
+    NotFound(String),
+    Full(String),
+}
+
+impl From<ParseError> for TournamentError {
+    fn from(err: ParseError) -> Self {
+        Self::Options(err)
+    }
+}
+
+impl fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentError::Options(err) => err.fmt(f),
+            TournamentError::InvalidPlayers(count) => write!(
+                f,
+                "Invalid number of players: {count} (2 to {})",
+                Tournament::MAX_PLAYERS
+            ),
+            TournamentError::MissingName => write!(f, "Name the tournament with tournament=NAME"),
+            TournamentError::MissingPlayers => {
+                write!(f, "Only the organizer chooses options, with players=N")
+            }
+            TournamentError::NotClassic(mode) => {
+                write!(f, "Tournaments can't be played in {} mode", mode.name())
+            }
+            TournamentError::Exists(name) => {
+                write!(f, "There is already a tournament called {name}")
+            }
+            TournamentError::NotFound(name) => write!(f, "There is no tournament called {name}"),
+            TournamentError::Full(name) => write!(f, "The tournament {name} is full"),
+        }
+    }
+}
+
+impl std::error::Error for TournamentError {}
+
+/// The bracket as seen by one viewer. See [`Tournament::view`].
+///
+/// Each section of the bracket is drawn with a column for each round, and later rounds spaced
+/// out between the matches that feed into them.
+pub struct BracketView<'a> {
+    tournament: &'a Tournament,
+    viewer: &'a Viewer<'a>,
+    own: Option<usize>,
+}
+
+impl BracketView<'_> {
+    const COLUMN_WIDTH: u16 = 22;
+    // Two lines for the players and a blank line between matches
+    const MATCH_HEIGHT: u16 = 3;
+
+    /// What is happening in the tournament, shown under the bracket.
+    fn status(&self) -> String {
+        let tournament = self.tournament;
+        let bracket = &tournament.bracket;
+        if let Some(champion) = bracket.champion() {
+            return format!(" {} won the tournament ", tournament.entrant_name(champion));
+        }
+        let waiting = tournament.players - tournament.entrants.len();
+        let playing = bracket.matches.iter().filter(|m| m.playing).count();
+        match (waiting, playing) {
+            (0, 0) => " Waiting for players to come back ".to_string(),
+            (0, 1) => " 1 match being played ".to_string(),
+            (0, playing) => format!(" {playing} matches being played "),
+            (1, _) => " Waiting for 1 more player ".to_string(),
+            (waiting, _) => format!(" Waiting for {waiting} more players "),
+        }
+    }
+
+    /// The line for one place in a bracket match.
+    fn entry_line(&self, index: usize, place: usize) -> Line<'static> {
+        let tournament = self.tournament;
+        let capabilities = self.viewer.capabilities;
+        let bracket_match = &tournament.bracket.matches[index];
+        let name = match tournament.bracket.entries(index)[place] {
+            Entry::Pending => capabilities.text("…", "...").to_string(),
+            Entry::Bye => "bye".to_string(),
+            Entry::Entrant(seed) => tournament.entrant_name(seed),
+        };
+        let score = match bracket_match.result {
+            Some(MatchResult {
+                score: Some(score), ..
+            }) => score[place].to_string(),
+            _ => String::new(),
+        };
+        let width = usize::from(Self::COLUMN_WIDTH) - 6;
+        let name: String = name.chars().take(width).collect();
+        let marker = if bracket_match.playing {
+            capabilities.text("▸", ">")
+        } else {
+            " "
+        };
+        let mut line = Line::from(format!("{marker}{name:<width$} {score:>3} "));
+        if let Some(result) = bracket_match.result {
+            line = if result.winner == place {
+                line.bold()
+            } else {
+                line.dim()
+            };
+        }
+        let entry = tournament.bracket.entries(index)[place];
+        if self.own.is_some_and(|seed| entry == Entry::Entrant(seed)) {
+            line = line.patch_style(capabilities.color(self.viewer.preferences.own_color));
+        } else if bracket_match.playing {
+            line = line.patch_style(capabilities.color(self.viewer.theme.score));
+        }
+        line
+    }
+
+    /// Draw one section of the bracket, returning the rows it used.
+    fn render_section(&self, section: Section, area: Rect, buf: &mut Buffer) -> u16 {
+        let matches: Vec<_> = (0..self.tournament.bracket.matches.len())
+            .filter(|&index| self.tournament.bracket.matches[index].section == section)
+            .collect();
+        let rounds = matches
+            .iter()
+            .map(|&index| self.tournament.bracket.matches[index].round + 1)
+            .max()
+            .unwrap_or_default();
+        if rounds == 0 {
+            return 0;
+        }
+        let in_round = |round| {
+            matches
+                .iter()
+                .copied()
+                .filter(move |&index| self.tournament.bracket.matches[index].round == round)
+        };
+        let most = (0..rounds)
+            .map(|round| in_round(round).count())
+            .max()
+            .unwrap_or_default() as u16;
+        let height = most * Self::MATCH_HEIGHT;
+        Line::from(section.name())
+            .bold()
+            .render(Rect { height: 1, ..area }, buf);
+        for round in 0..rounds {
+            let count = in_round(round).count() as u16;
+            // space the matches out evenly across the height of the section
+            let spacing = height / count.max(1);
+            let x = area.x + round as u16 * Self::COLUMN_WIDTH;
+            for (i, index) in in_round(round).enumerate() {
+                let y = area.y + 1 + i as u16 * spacing + (spacing - Self::MATCH_HEIGHT) / 2;
+                for place in 0..2 {
+                    let row = Rect::new(x, y + place as u16, Self::COLUMN_WIDTH, 1);
+                    self.entry_line(index, place)
+                        .render(row.intersection(area), buf);
+                }
+            }
+        }
+        height + 1
+    }
+}
+
+impl Widget for BracketView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let theme = self.viewer.theme;
+        let capabilities = self.viewer.capabilities;
+        Clear.render(area, buf);
+        let tournament = self.tournament;
+        let block = Block::bordered()
+            .border_set(capabilities.border_set())
+            .title(format!(
+                " {}{}{} ",
+                tournament.name,
+                capabilities.text(" · ", " - "),
+                tournament.bracket.elimination.name()
+            ))
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(self.status()).centered())
+            .style((
+                capabilities.color(theme.foreground),
+                capabilities.color(theme.background),
+            ));
+        let mut inner = block.inner(area).inner(Margin::new(1, 0));
+        block.render(area, buf);
+        for section in Section::ALL {
+            let used = self.render_section(section, inner, buf);
+            if used > 0 {
+                inner.y += used;
+                inner.height = inner.height.saturating_sub(used);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_elimination_with_byes() {
+        let mut bracket = Bracket::new(Elimination::Single, 3);
+        // the top seed has a bye into the final
+        assert_eq!(bracket.matches.len(), 3);
+        assert_eq!(bracket.ready(), vec![(1, [1, 2])]);
+        bracket.record(1, 2, Some([4, 11]));
+        assert_eq!(bracket.ready(), vec![(2, [0, 2])]);
+        bracket.record(2, 0, Some([11, 9]));
+        assert_eq!(bracket.champion(), Some(0));
+    }
+
+    #[test]
+    fn double_elimination() {
+        let mut bracket = Bracket::new(Elimination::Double, 4);
+        // two rounds in each bracket, the grand final and its possible rematch
+        assert_eq!(bracket.matches.len(), 7);
+        assert_eq!(bracket.ready(), vec![(0, [0, 3]), (1, [1, 2])]);
+        bracket.record(0, 0, None);
+        bracket.record(1, 1, None);
+        assert_eq!(bracket.ready(), vec![(2, [0, 1]), (3, [3, 2])]);
+        bracket.record(2, 0, None);
+        bracket.record(3, 2, None);
+        assert_eq!(bracket.ready(), vec![(4, [2, 1])]);
+        bracket.record(4, 1, None);
+        assert_eq!(bracket.ready(), vec![(5, [0, 1])]);
+
+        // the winner of the winners bracket wins the tournament straight away
+        let mut won = bracket.clone();
+        won.record(5, 0, None);
+        assert_eq!(won.ready(), vec![]);
+        assert_eq!(won.entries(6), [Entry::Entrant(0), Entry::Bye]);
+        assert_eq!(won.champion(), Some(0));
+
+        // but if they lose, they have only lost once, so the final is played again
+        bracket.record(5, 1, None);
+        assert_eq!(bracket.champion(), None);
+        assert_eq!(bracket.ready(), vec![(6, [1, 0])]);
+        bracket.record(6, 1, None);
+        assert_eq!(bracket.champion(), Some(1));
+    }
+
+    #[test]
+    fn parse_requests() {
+        let levels = Levels::default();
+        assert!(matches!(
+            TournamentRequest::parse("doubles", &levels),
+            Ok(None)
+        ));
+        assert!(matches!(
+            TournamentRequest::parse("tournament=cup", &levels),
+            Ok(Some(TournamentRequest::Enter(name))) if name == "cup"
+        ));
+        let Ok(Some(TournamentRequest::Create(tournament))) = TournamentRequest::parse(
+            "tournament=cup players=6 double-elimination power-ups",
+            &levels,
+        ) else {
+            panic!("expected a tournament to be created");
+        };
+        assert_eq!(tournament.players, 6);
+        assert_eq!(tournament.bracket.elimination, Elimination::Double);
+        assert!(tournament.config.power_ups);
+        assert_eq!(
+            TournamentRequest::parse("tournament=cup players=4 doubles", &levels).unwrap_err(),
+            TournamentError::NotClassic(Mode::Doubles)
+        );
+        assert_eq!(
+            TournamentRequest::parse("tournament=cup power-ups", &levels).unwrap_err(),
+            TournamentError::MissingPlayers
+        );
+    }
+
+    #[test]
+    fn ascii_bracket_waiting_for_walkovers() {
+        use crate::{
+            capabilities::Capabilities, effects::Effects, theme::Theme, viewer::Preferences,
+        };
+
+        let mut tournament = Tournament::new("cup", Elimination::Single, 4, MatchConfig::default());
+        for (client_id, name) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            tournament.enter(name, name, client_id).unwrap();
+        }
+        let (preferences, theme, effects) =
+            (Preferences::default(), Theme::classic(), Effects::default());
+        let viewer = Viewer {
+            role: crate::viewer::Role::Spectator,
+            preferences: &preferences,
+            capabilities: Capabilities::from_term("vt100"),
+            theme: &theme,
+            cell_aspect: 2.0,
+            graphics: false,
+            effects: &effects,
+        };
+        let view = tournament.view(&viewer, None);
+        assert_eq!(view.status(), " Waiting for players to come back ");
+
+        let area = Rect::new(0, 0, 80, 20);
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        let text: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(text.is_ascii());
+        assert!(text.contains("cup - single-elimination"));
+        assert!(text.contains("..."));
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ratatui::prelude::*;
+
+use crate::{ball::BallGlyphs, capabilities::Capabilities, effects::Effects, theme::Theme};
+
+/// How a client takes part in the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Controls the paddle in the given slot (see [`Mode::sides`](crate::mode::Mode::sides)).
+    Player(usize),
+    Spectator,
+}
+
+/// A player's display preferences, persisted with their [`Profile`](crate::profile::Profile).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preferences {
+    /// Flip the court horizontally so the player's own paddle is always on the left.
+    pub mirror: bool,
+    /// The color used to highlight the player's own paddle and name.
+    pub own_color: Color,
+    pub ball_glyphs: BallGlyphs,
+    /// The name of the [`Theme`] used to draw the court.
+    pub theme: String,
+    /// Draw the ball and paddles as images when the terminal supports it.
+    pub graphics: bool,
+    /// Draw dotted lines across the court in addition to the net.
+    pub court_markings: bool,
+    /// Draw a fading trail behind the ball.
+    pub trail: bool,
+    /// Flash a paddle when it returns the ball.
+    pub hit_flash: bool,
+    /// Shake and flash the court when a point is scored.
+    pub score_shake: bool,
+    /// Silence all sounds and notifications, regardless of the settings below.
+    pub muted: bool,
+    pub bell_paddle_hit: bool,
+    pub bell_wall_bounce: bool,
+    pub bell_point: bool,
+    pub bell_match: bool,
+    /// Show a desktop notification when a match is won.
+    pub desktop_notifications: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            mirror: false,
+            own_color: Color::Yellow,
+            ball_glyphs: BallGlyphs::default(),
+            theme: Theme::default().name,
+            graphics: true,
+            court_markings: false,
+            trail: true,
+            hit_flash: true,
+            score_shake: true,
+            muted: false,
+            bell_paddle_hit: false,
+            bell_wall_bounce: false,
+            bell_point: true,
+            bell_match: true,
+            desktop_notifications: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// The colors players can choose from for their own paddle.
+    pub const OWN_COLORS: [Color; 6] = [
+        Color::Yellow,
+        Color::Cyan,
+        Color::Green,
+        Color::Magenta,
+        Color::LightRed,
+        Color::LightBlue,
+    ];
+}
+
+/// Everything that affects how the game is drawn for one client.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer<'a> {
+    pub role: Role,
+    pub preferences: &'a Preferences,
+    pub capabilities: Capabilities,
+    pub theme: &'a Theme,
+    /// The height of the client's terminal cells relative to their width.
+    pub cell_aspect: f32,
+    /// Whether the ball and paddles are drawn separately as graphics rather than as characters.
+    pub graphics: bool,
+    pub effects: &'a Effects,
+}
+
+/// Short messages shown to a single client, such as which side they are playing on.
+///
+/// Each notification is shown for a few seconds and then expires.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    messages: VecDeque<(String, Instant)>,
+}
+
+impl Notifications {
+    const DURATION: Duration = Duration::from_secs(3);
+    const MAX_SHOWN: usize = 3;
+
+    pub fn push(&mut self, message: impl Into<String>) {
+        self.messages.push_back((message.into(), Instant::now()));
+        if self.messages.len() > Self::MAX_SHOWN {
+            self.messages.pop_front();
+        }
+    }
+
+    /// Drop notifications that have been shown for long enough.
+    pub fn expire(&mut self) {
+        self.messages
+            .retain(|(_, shown)| shown.elapsed() < Self::DURATION);
+    }
+
+    /// The line for each notification, in the row of the given area it is drawn in.
+    fn lines(
+        &self,
+        area: Rect,
+        capabilities: Capabilities,
+    ) -> impl Iterator<Item = (Line<'_>, Rect)> {
+        let area = area.inner(Margin::new(2, 2));
+        let style = (
+            capabilities.color(Color::Black),
+            capabilities.color(Color::White),
+        );
+        self.messages
+            .iter()
+            .zip(area.rows())
+            .map(move |((message, _), row)| {
+                let line = Line::from(Span::styled(format!(" {message} "), style));
+                (line.centered(), row)
+            })
+    }
+
+    /// The parts of the given area the notifications are drawn over.
+    pub fn areas(&self, area: Rect) -> Vec<Rect> {
+        self.lines(area, Capabilities::default())
+            .map(|(line, row)| {
+                let width = (line.width() as u16).min(row.width);
+                Rect {
+                    x: row.x + (row.width - width) / 2,
+                    width,
+                    ..row
+                }
+            })
+            .collect()
+    }
+
+    /// A widget that draws the notifications centered near the top of the area, newest last.
+    pub fn widget(&self, capabilities: Capabilities) -> NotificationsWidget<'_> {
+        NotificationsWidget {
+            notifications: self,
+            capabilities,
+        }
+    }
+}
+
+/// Draws [`Notifications`] for one client. See [`Notifications::widget`].
+pub struct NotificationsWidget<'a> {
+    notifications: &'a Notifications,
+    capabilities: Capabilities,
+}
+
+impl Widget for NotificationsWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for (line, row) in self.notifications.lines(area, self.capabilities) {
+            line.render(row, buf);
+        }
+    }
+}